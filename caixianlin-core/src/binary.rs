@@ -0,0 +1,731 @@
+//! Binary protocol for controlling the device with low latency.
+//!
+//! Every frame starts and ends with [`DELIMITER`], which never appears in typed text, so the
+//! binary protocol can share the serial port with the text shell. The bytes in between are COBS
+//! encoded and contain a sequence number, the message type, the message body and a CRC16 of all
+//! of them. Every request is answered with a response carrying the same sequence number. A
+//! request that is received again with the same sequence number is answered again without being
+//! executed a second time, so lost responses can be retried safely.
+
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use crate::{
+    command::MAX_TRANSMIT_DURATION,
+    packet::{Action, Channel, Packet},
+    protocol::ProtocolKind,
+    queue::Repeat,
+    status::Status,
+};
+
+/// Byte that starts and ends every frame
+pub const DELIMITER: u8 = 0x00;
+
+/// Longest encoded frame that is accepted, without the delimiters
+pub const MAX_FRAME_LENGTH: usize = 64;
+
+/// Longest pause between two bytes of a frame, after it the frame is dropped
+///
+/// Hosts send whole frames at once, so only a stray delimiter leaves the link waiting that long.
+pub const FRAME_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Request types
+const SET_CONFIG: u8 = 0x01;
+const TRANSMIT: u8 = 0x02;
+const STOP: u8 = 0x03;
+const STATUS: u8 = 0x04;
+
+/// Response types
+const ACK: u8 = 0x80;
+const ERROR: u8 = 0x81;
+const STATUS_REPORT: u8 = 0x82;
+
+/// Repeat kinds of a transmit request
+const REPEAT_COUNT: u8 = 0;
+const REPEAT_FOR: u8 = 1;
+
+/// Calculate the CRC-16/CCITT-FALSE of some bytes
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xffff;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Encode bytes with consistent overhead byte stuffing, so the output contains no zeros
+pub fn cobs_encode(data: &[u8], output: &mut Vec<u8>) {
+    let mut code_index = output.len();
+    output.push(0);
+    let mut code = 1;
+    for byte in data {
+        if *byte != 0 {
+            output.push(*byte);
+            code += 1;
+        }
+        if *byte == 0 || code == 0xff {
+            output[code_index] = code;
+            code_index = output.len();
+            output.push(0);
+            code = 1;
+        }
+    }
+    output[code_index] = code;
+}
+
+/// Decode bytes encoded with [`cobs_encode`]
+pub fn cobs_decode(data: &[u8]) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(data.len());
+    let mut index = 0;
+    while index < data.len() {
+        let code = data[index] as usize;
+        if code == 0 || index + code > data.len() {
+            return None;
+        }
+        output.extend_from_slice(&data[index + 1..index + code]);
+        index += code;
+        if code < 0xff && index < data.len() {
+            output.push(0);
+        }
+    }
+    Some(output)
+}
+
+/// Wrap a message into a frame, including both delimiters
+pub fn pack(sequence: u8, message: &[u8], output: &mut Vec<u8>) {
+    let mut payload = Vec::with_capacity(message.len() + 3);
+    payload.push(sequence);
+    payload.extend_from_slice(message);
+    payload.extend_from_slice(&crc16(&payload).to_le_bytes());
+
+    output.push(DELIMITER);
+    cobs_encode(&payload, output);
+    output.push(DELIMITER);
+}
+
+/// Unwrap a frame received between two delimiters
+///
+/// Returns the sequence number and the message.
+pub fn unpack(frame: &[u8]) -> Result<(u8, Vec<u8>), ErrorCode> {
+    let mut payload = cobs_decode(frame).ok_or(ErrorCode::Framing)?;
+    // sequence number, message type and checksum
+    if payload.len() < 4 {
+        return Err(ErrorCode::Length);
+    }
+    let checksum_start = payload.len() - 2;
+    let checksum = u16::from_le_bytes([payload[checksum_start], payload[checksum_start + 1]]);
+    if crc16(&payload[..checksum_start]) != checksum {
+        return Err(ErrorCode::Checksum);
+    }
+    payload.truncate(checksum_start);
+    let sequence = payload.remove(0);
+    Ok((sequence, payload))
+}
+
+/// Reasons a request was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[repr(u8)]
+pub enum ErrorCode {
+    /// The frame is not valid COBS or too long
+    #[error("invalid framing")]
+    Framing = 1,
+    /// The checksum does not match
+    #[error("checksum mismatch")]
+    Checksum = 2,
+    /// The message type is not known
+    #[error("unknown message type")]
+    UnknownType = 3,
+    /// The message is too short or too long for its type
+    #[error("invalid message length")]
+    Length = 4,
+    /// A field of the message is out of range
+    #[error("invalid value")]
+    InvalidValue = 5,
+    /// The packet cannot be encoded with the protocol
+    #[error("packet cannot be encoded")]
+    Encode = 6,
+    /// The transmission violates the safety limits
+    #[error("safety limit exceeded")]
+    Limit = 7,
+}
+
+impl TryFrom<u8> for ErrorCode {
+    type Error = ErrorCode;
+
+    /// Convert a u8 to an ErrorCode
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(ErrorCode::Framing),
+            2 => Ok(ErrorCode::Checksum),
+            3 => Ok(ErrorCode::UnknownType),
+            4 => Ok(ErrorCode::Length),
+            5 => Ok(ErrorCode::InvalidValue),
+            6 => Ok(ErrorCode::Encode),
+            7 => Ok(ErrorCode::Limit),
+            _ => Err(ErrorCode::InvalidValue),
+        }
+    }
+}
+
+/// Messages sent to the device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// Set and store the configured packet and protocol
+    SetConfig {
+        protocol: ProtocolKind,
+        packet: Packet,
+    },
+    /// Transmit a packet, independent of the configuration
+    Transmit {
+        protocol: ProtocolKind,
+        packet: Packet,
+        repeat: Repeat,
+    },
+    /// Drop all queued transmissions and abort the current one
+    Stop,
+    /// Report the status
+    Status,
+}
+
+/// Messages sent by the device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// The request was executed
+    Ack,
+    /// The request was rejected
+    Error(ErrorCode),
+    /// Answer to a status request
+    Status(StatusReport),
+}
+
+/// Status as carried by the binary protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusReport {
+    /// Configured protocol
+    pub protocol: ProtocolKind,
+    /// Configured packet
+    pub packet: Packet,
+    /// Amount of jobs in the transmit queue
+    pub queue_depth: u16,
+    /// Whether a frame is being transmitted right now
+    pub transmitting: bool,
+    /// Time since boot in milliseconds, wrapping after 49 days
+    pub uptime_ms: u32,
+}
+
+impl From<&Status> for StatusReport {
+    fn from(status: &Status) -> Self {
+        Self {
+            protocol: status.protocol,
+            packet: Packet {
+                id: status.id,
+                channel: status.channel,
+                action: status.action,
+                intensity: status.intensity,
+            },
+            queue_depth: status.queue_depth.try_into().unwrap_or(u16::MAX),
+            transmitting: status.transmitting,
+            uptime_ms: status.uptime.as_millis() as u32,
+        }
+    }
+}
+
+impl StatusReport {
+    /// Append the fields in the order protocol, packet, queue depth, transmitting, uptime
+    pub fn encode(&self, output: &mut Vec<u8>) {
+        output.push(self.protocol as u8);
+        write_packet(&self.packet, output);
+        output.extend_from_slice(&self.queue_depth.to_le_bytes());
+        output.push(self.transmitting as u8);
+        output.extend_from_slice(&self.uptime_ms.to_le_bytes());
+    }
+}
+
+/// Append the fields of a packet in the order id, channel, action, intensity
+fn write_packet(packet: &Packet, output: &mut Vec<u8>) {
+    output.extend_from_slice(&packet.id.to_le_bytes());
+    output.extend_from_slice(&[packet.channel as u8, packet.action as u8, packet.intensity]);
+}
+
+/// Read the fields written by [`write_packet`]
+fn read_packet(bytes: &[u8]) -> Result<Packet, ErrorCode> {
+    let [id_low, id_high, channel, action, intensity] = bytes else {
+        return Err(ErrorCode::Length);
+    };
+    if *intensity > 99 {
+        return Err(ErrorCode::InvalidValue);
+    }
+    Ok(Packet {
+        id: u16::from_le_bytes([*id_low, *id_high]),
+        channel: Channel::try_from(*channel).map_err(|_| ErrorCode::InvalidValue)?,
+        action: Action::try_from(*action).map_err(|_| ErrorCode::InvalidValue)?,
+        intensity: *intensity,
+    })
+}
+
+/// Read the repeat kind and value of a transmit request
+pub(crate) fn read_repeat(kind: u8, value: u32) -> Result<Repeat, ErrorCode> {
+    match kind {
+        REPEAT_COUNT => Ok(Repeat::Count(
+            u16::try_from(value).map_err(|_| ErrorCode::InvalidValue)?,
+        )),
+        REPEAT_FOR => {
+            let duration = Duration::from_millis(value as u64);
+            if duration > MAX_TRANSMIT_DURATION {
+                return Err(ErrorCode::InvalidValue);
+            }
+            Ok(Repeat::For(duration))
+        }
+        _ => Err(ErrorCode::InvalidValue),
+    }
+}
+
+/// Read a protocol byte
+fn read_protocol(byte: u8) -> Result<ProtocolKind, ErrorCode> {
+    ProtocolKind::try_from(byte).map_err(|_| ErrorCode::InvalidValue)
+}
+
+impl Request {
+    /// Append the message type and body
+    pub fn encode(&self, output: &mut Vec<u8>) {
+        match self {
+            Request::SetConfig { protocol, packet } => {
+                output.extend_from_slice(&[SET_CONFIG, *protocol as u8]);
+                write_packet(packet, output);
+            }
+            Request::Transmit {
+                protocol,
+                packet,
+                repeat,
+            } => {
+                output.extend_from_slice(&[TRANSMIT, *protocol as u8]);
+                write_packet(packet, output);
+                let (kind, value) = match repeat {
+                    Repeat::Count(count) => (REPEAT_COUNT, *count as u32),
+                    Repeat::For(duration) => (REPEAT_FOR, duration.as_millis() as u32),
+                };
+                output.push(kind);
+                output.extend_from_slice(&value.to_le_bytes());
+            }
+            Request::Stop => output.push(STOP),
+            Request::Status => output.push(STATUS),
+        }
+    }
+
+    /// Decode a message type and body
+    pub fn decode(message: &[u8]) -> Result<Self, ErrorCode> {
+        let Some((kind, body)) = message.split_first() else {
+            return Err(ErrorCode::Length);
+        };
+        match (*kind, body) {
+            (SET_CONFIG, [protocol, packet @ ..]) => Ok(Request::SetConfig {
+                protocol: read_protocol(*protocol)?,
+                packet: read_packet(packet)?,
+            }),
+            (TRANSMIT, [protocol, packet @ .., kind, a, b, c, d]) => {
+                let value = u32::from_le_bytes([*a, *b, *c, *d]);
+                Ok(Request::Transmit {
+                    protocol: read_protocol(*protocol)?,
+                    packet: read_packet(packet)?,
+                    repeat: read_repeat(*kind, value)?,
+                })
+            }
+            (STOP, []) => Ok(Request::Stop),
+            (STATUS, []) => Ok(Request::Status),
+            (SET_CONFIG | TRANSMIT | STOP | STATUS, _) => Err(ErrorCode::Length),
+            _ => Err(ErrorCode::UnknownType),
+        }
+    }
+}
+
+impl Response {
+    /// Append the message type and body
+    pub fn encode(&self, output: &mut Vec<u8>) {
+        match self {
+            Response::Ack => output.push(ACK),
+            Response::Error(code) => output.extend_from_slice(&[ERROR, *code as u8]),
+            Response::Status(status) => {
+                output.push(STATUS_REPORT);
+                status.encode(output);
+            }
+        }
+    }
+
+    /// Decode a message type and body
+    pub fn decode(message: &[u8]) -> Result<Self, ErrorCode> {
+        let Some((kind, body)) = message.split_first() else {
+            return Err(ErrorCode::Length);
+        };
+        match (*kind, body) {
+            (ACK, []) => Ok(Response::Ack),
+            (ERROR, [code]) => Ok(Response::Error(ErrorCode::try_from(*code)?)),
+            (STATUS_REPORT, [protocol, packet @ .., q0, q1, transmitting, u0, u1, u2, u3])
+                if packet.len() == 5 =>
+            {
+                Ok(Response::Status(StatusReport {
+                    protocol: read_protocol(*protocol)?,
+                    packet: read_packet(packet)?,
+                    queue_depth: u16::from_le_bytes([*q0, *q1]),
+                    transmitting: *transmitting != 0,
+                    uptime_ms: u32::from_le_bytes([*u0, *u1, *u2, *u3]),
+                }))
+            }
+            (ACK | ERROR | STATUS_REPORT, _) => Err(ErrorCode::Length),
+            _ => Err(ErrorCode::UnknownType),
+        }
+    }
+}
+
+/// What to do with a frame received by a [`Link`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Incoming {
+    /// A new request that has to be executed and answered with [`Link::reply`]
+    Request { sequence: u8, request: Request },
+    /// Bytes that have to be sent back without executing anything
+    ///
+    /// This is the repeated response to a retransmitted request or an error response.
+    Reply(Vec<u8>),
+}
+
+/// Device side of the binary protocol
+///
+/// Collects frames from the bytes received on the serial port and remembers the last response,
+/// so retransmitted requests are not executed twice.
+#[derive(Debug, Default)]
+pub struct Link {
+    /// Bytes of the frame being received
+    buffer: Vec<u8>,
+    /// Whether a frame is being received
+    receiving: bool,
+    /// When the last byte of the frame was received
+    last_byte: Duration,
+    /// Sequence number and message of the last request
+    last_request: Option<(u8, Vec<u8>)>,
+    /// Frame of the last response
+    last_reply: Vec<u8>,
+}
+
+impl Link {
+    /// Create a new link
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a frame was started, so bytes received now belong to the binary protocol
+    ///
+    /// A frame that received nothing for [`FRAME_TIMEOUT`] is given up, so the text typed after
+    /// a stray delimiter reaches the shell.
+    pub fn is_receiving(&self, now: Duration) -> bool {
+        self.receiving && now.saturating_sub(self.last_byte) < FRAME_TIMEOUT
+    }
+
+    /// Handle a byte received on the serial port at the given time
+    ///
+    /// Only bytes received while [`Link::is_receiving`] and the [`DELIMITER`] starting a frame
+    /// should be passed in, all others belong to the text shell.
+    pub fn push(&mut self, byte: u8, now: Duration) -> Option<Incoming> {
+        let receiving = self.is_receiving(now);
+        self.last_byte = now;
+        if !receiving {
+            self.receiving = byte == DELIMITER;
+            self.buffer.clear();
+            return None;
+        }
+        if byte != DELIMITER {
+            self.buffer.push(byte);
+            if self.buffer.len() > MAX_FRAME_LENGTH {
+                // give the port back to the text shell
+                self.receiving = false;
+                return Some(Incoming::Reply(error_frame(0, ErrorCode::Framing)));
+            }
+            return None;
+        }
+        // the end of one frame may be directly followed by the start of the next one
+        if self.buffer.is_empty() {
+            return None;
+        }
+        self.receiving = false;
+
+        let (sequence, message) = match unpack(&self.buffer) {
+            Ok(unpacked) => unpacked,
+            Err(code) => {
+                // the sequence number cannot be trusted, but is the best guess
+                let sequence = cobs_decode(&self.buffer)
+                    .and_then(|payload| payload.first().copied())
+                    .unwrap_or(0);
+                return Some(Incoming::Reply(error_frame(sequence, code)));
+            }
+        };
+        if self
+            .last_request
+            .as_ref()
+            .is_some_and(|(last_sequence, last_message)| {
+                *last_sequence == sequence && *last_message == message
+            })
+        {
+            return Some(Incoming::Reply(self.last_reply.clone()));
+        }
+
+        match Request::decode(&message) {
+            Ok(request) => {
+                self.last_request = Some((sequence, message));
+                Some(Incoming::Request { sequence, request })
+            }
+            Err(code) => Some(Incoming::Reply(error_frame(sequence, code))),
+        }
+    }
+
+    /// Frame the response to a request and remember it for retransmissions
+    pub fn reply(&mut self, sequence: u8, response: &Response) -> Vec<u8> {
+        let mut message = Vec::new();
+        response.encode(&mut message);
+        self.last_reply.clear();
+        pack(sequence, &message, &mut self.last_reply);
+        self.last_reply.clone()
+    }
+}
+
+/// Frame an error response
+fn error_frame(sequence: u8, code: ErrorCode) -> Vec<u8> {
+    let mut message = Vec::new();
+    Response::Error(code).encode(&mut message);
+    let mut frame = Vec::new();
+    pack(sequence, &message, &mut frame);
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn packet() -> Packet {
+        Packet {
+            id: 1234,
+            channel: Channel::One,
+            action: Action::Shock,
+            intensity: 30,
+        }
+    }
+
+    /// Frame a request the way a host would
+    fn request_frame(sequence: u8, request: &Request) -> Vec<u8> {
+        let mut message = Vec::new();
+        request.encode(&mut message);
+        let mut frame = Vec::new();
+        pack(sequence, &message, &mut frame);
+        frame
+    }
+
+    /// Feed bytes into a link and collect everything it returns
+    fn feed(link: &mut Link, bytes: &[u8]) -> Vec<Incoming> {
+        bytes
+            .iter()
+            .filter_map(|byte| link.push(*byte, Duration::ZERO))
+            .collect()
+    }
+
+    /// Unpack and decode a response frame including its delimiters
+    fn response(frame: &[u8]) -> (u8, Response) {
+        assert_eq!(frame.first(), Some(&DELIMITER));
+        assert_eq!(frame.last(), Some(&DELIMITER));
+        let (sequence, message) = unpack(&frame[1..frame.len() - 1]).unwrap();
+        (sequence, Response::decode(&message).unwrap())
+    }
+
+    /// Test the CRC against the standard check value
+    #[test]
+    fn calculates_crc() {
+        assert_eq!(crc16(b"123456789"), 0x29b1);
+        assert_eq!(crc16(&[]), 0xffff);
+    }
+
+    /// Test COBS with zeros and long runs
+    #[test]
+    fn stuffs_bytes() {
+        let cases: [&[u8]; 5] = [&[], &[0], &[0, 0], &[0x11, 0x22, 0x00, 0x33], &[1; 300]];
+        for case in cases {
+            let mut encoded = Vec::new();
+            cobs_encode(case, &mut encoded);
+            assert!(!encoded.contains(&0));
+            assert_eq!(cobs_decode(&encoded).as_deref(), Some(case));
+        }
+
+        let mut encoded = Vec::new();
+        cobs_encode(&[0x11, 0x22, 0x00, 0x33], &mut encoded);
+        assert_eq!(encoded, [0x03, 0x11, 0x22, 0x02, 0x33]);
+        assert_eq!(cobs_decode(&[0x05, 0x11]), None);
+    }
+
+    /// Test that all messages survive encoding and decoding
+    #[test]
+    fn round_trips_messages() {
+        let requests = [
+            Request::SetConfig {
+                protocol: ProtocolKind::Petrainer,
+                packet: packet(),
+            },
+            Request::Transmit {
+                protocol: ProtocolKind::CaiXianLin,
+                packet: packet(),
+                repeat: Repeat::Count(4),
+            },
+            Request::Transmit {
+                protocol: ProtocolKind::CaiXianLin,
+                packet: packet(),
+                repeat: Repeat::For(Duration::from_millis(1500)),
+            },
+            Request::Stop,
+            Request::Status,
+        ];
+        for request in requests {
+            let mut message = Vec::new();
+            request.encode(&mut message);
+            assert_eq!(Request::decode(&message), Ok(request));
+        }
+
+        let responses = [
+            Response::Ack,
+            Response::Error(ErrorCode::Encode),
+            Response::Status(StatusReport {
+                protocol: ProtocolKind::CaiXianLin,
+                packet: packet(),
+                queue_depth: 3,
+                transmitting: true,
+                uptime_ms: 123_456,
+            }),
+        ];
+        for response in responses {
+            let mut message = Vec::new();
+            response.encode(&mut message);
+            assert_eq!(Response::decode(&message), Ok(response));
+        }
+    }
+
+    /// Test that malformed messages are rejected
+    #[test]
+    fn rejects_invalid_messages() {
+        assert_eq!(Request::decode(&[]), Err(ErrorCode::Length));
+        assert_eq!(Request::decode(&[0x42]), Err(ErrorCode::UnknownType));
+        assert_eq!(Request::decode(&[STOP, 1]), Err(ErrorCode::Length));
+        assert_eq!(
+            Request::decode(&[SET_CONFIG, 0, 1, 0, 3, 1, 30]),
+            Err(ErrorCode::InvalidValue)
+        );
+        assert_eq!(
+            Request::decode(&[SET_CONFIG, 0, 1, 0, 1, 1, 100]),
+            Err(ErrorCode::InvalidValue)
+        );
+        assert_eq!(
+            Request::decode(&[TRANSMIT, 0, 1, 0, 1, 1, 30, REPEAT_COUNT, 0, 0, 1, 0]),
+            Err(ErrorCode::InvalidValue)
+        );
+    }
+
+    /// Test that the link executes requests once and answers retransmissions again
+    #[test]
+    fn deduplicates_requests() {
+        let mut link = Link::new();
+        let frame = request_frame(7, &Request::Stop);
+
+        assert_eq!(
+            feed(&mut link, &frame),
+            [Incoming::Request {
+                sequence: 7,
+                request: Request::Stop
+            }]
+        );
+        assert!(!link.is_receiving(Duration::ZERO));
+        let reply = link.reply(7, &Response::Ack);
+        assert_eq!(response(&reply), (7, Response::Ack));
+
+        // the same request again is only answered
+        assert_eq!(feed(&mut link, &frame), [Incoming::Reply(reply)]);
+
+        // a new sequence number is executed again
+        let frame = request_frame(8, &Request::Stop);
+        assert!(matches!(
+            feed(&mut link, &frame)[..],
+            [Incoming::Request { sequence: 8, .. }]
+        ));
+    }
+
+    /// Test that corrupted frames are answered with errors
+    #[test]
+    fn reports_corrupted_frames() {
+        let mut link = Link::new();
+        let mut frame = request_frame(3, &Request::Status);
+        let last = frame.len() - 2;
+        frame[last] ^= 0x01;
+
+        let incoming = feed(&mut link, &frame);
+        let [Incoming::Reply(reply)] = &incoming[..] else {
+            panic!("expected a reply, got {:?}", incoming);
+        };
+        assert_eq!(response(reply), (3, Response::Error(ErrorCode::Checksum)));
+
+        // frames that never end are dropped
+        let mut bytes = vec![DELIMITER];
+        bytes.extend_from_slice(&[1; MAX_FRAME_LENGTH + 1]);
+        let incoming = feed(&mut link, &bytes);
+        assert_eq!(incoming.len(), 1);
+        assert!(!link.is_receiving(Duration::ZERO));
+    }
+
+    /// Test that text typed after a stray delimiter reaches the shell
+    #[test]
+    fn gives_up_stray_delimiters() {
+        let mut link = Link::new();
+        assert_eq!(link.push(DELIMITER, Duration::ZERO), None);
+        assert!(link.is_receiving(Duration::from_millis(49)));
+
+        // route the bytes like the firmware does
+        let mut text = Vec::new();
+        for (index, byte) in b"stop\n".iter().enumerate() {
+            let now = FRAME_TIMEOUT + Duration::from_millis(index as u64);
+            if link.is_receiving(now) || *byte == DELIMITER {
+                assert_eq!(link.push(*byte, now), None);
+            } else {
+                text.push(*byte);
+            }
+        }
+        assert_eq!(text, b"stop\n");
+
+        // frames are received as usual afterwards
+        assert!(matches!(
+            feed(&mut link, &request_frame(1, &Request::Stop))[..],
+            [Incoming::Request { sequence: 1, .. }]
+        ));
+    }
+
+    /// Test that frames can follow each other directly
+    #[test]
+    fn handles_back_to_back_frames() {
+        let mut link = Link::new();
+        let mut bytes = request_frame(1, &Request::Status);
+        bytes.extend(request_frame(2, &Request::Stop));
+        bytes.insert(0, DELIMITER);
+
+        let incoming = feed(&mut link, &bytes);
+        assert_eq!(
+            incoming,
+            [
+                Incoming::Request {
+                    sequence: 1,
+                    request: Request::Status
+                },
+                Incoming::Request {
+                    sequence: 2,
+                    request: Request::Stop
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Display};
+
+use crate::{
+    limits::{Limits, Target, ENCODED_LIMITS_LENGTH},
+    packet::Channel,
+    protocol::ProtocolKind,
+    warning::{Warning, ENCODED_WARNING_LENGTH},
+};
+
+/// Most collars the address book holds
+pub const MAX_COLLARS: usize = 16;
+
+/// Longest name of a collar
+pub const MAX_NAME_LENGTH: usize = 15;
+
+/// Version of the stored address book format
+const FORMAT_VERSION: u8 = 1;
+
+/// Whether a name can be used for a collar
+///
+/// Names are short and consist of letters, digits, `-` and `_`, so they never need quoting.
+pub fn is_valid_name(name: &str) -> bool {
+    (1..=MAX_NAME_LENGTH).contains(&name.len())
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+/// A collar that can be addressed by name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collar {
+    /// Name of the collar
+    pub name: String,
+    /// Shocker ID
+    pub id: u16,
+    /// Channel the collar listens on
+    pub channel: Channel,
+    /// Protocol of the collar
+    pub protocol: ProtocolKind,
+    /// Safety limits of the collar, on top of the global ones
+    pub limits: Limits,
+    /// Burst that precedes every shock to the collar
+    pub warning: Option<Warning>,
+}
+
+impl Collar {
+    /// Identity the collar is addressed with
+    pub fn target(&self) -> Target {
+        Target {
+            protocol: self.protocol,
+            id: self.id,
+            channel: self.channel,
+        }
+    }
+}
+
+impl Display for Collar {
+    /// Describe the collar without its name
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ID {} on channel {}, {}",
+            self.id,
+            self.channel as u8,
+            self.protocol.name()
+        )?;
+        if self.limits != Limits::NONE {
+            write!(f, ", {}", self.limits)?;
+        }
+        if let Some(warning) = &self.warning {
+            write!(f, ", warning: {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+/// Errors of the address book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum AddressBookError {
+    /// No more collars fit into the address book
+    #[error("The address book is full, remove a collar first")]
+    Full,
+    /// The stored address book cannot be read
+    #[error("Stored address book is corrupted")]
+    Corrupted,
+}
+
+/// Collars saved under their names
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddressBook {
+    /// Collars in the order they were added
+    collars: Vec<Collar>,
+}
+
+impl AddressBook {
+    /// Create an empty address book
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look up a collar by name
+    pub fn get(&self, name: &str) -> Option<&Collar> {
+        self.collars.iter().find(|collar| collar.name == name)
+    }
+
+    /// Add a collar, replacing the one with the same name
+    pub fn insert(&mut self, collar: Collar) -> Result<(), AddressBookError> {
+        if let Some(existing) = self.collars.iter_mut().find(|c| c.name == collar.name) {
+            *existing = collar;
+            return Ok(());
+        }
+        if self.collars.len() >= MAX_COLLARS {
+            return Err(AddressBookError::Full);
+        }
+        self.collars.push(collar);
+        Ok(())
+    }
+
+    /// Remove a collar by name, returning it
+    pub fn remove(&mut self, name: &str) -> Option<Collar> {
+        let index = self.collars.iter().position(|collar| collar.name == name)?;
+        Some(self.collars.remove(index))
+    }
+
+    /// All collars in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = &Collar> {
+        self.collars.iter()
+    }
+
+    /// Amount of collars
+    pub fn len(&self) -> usize {
+        self.collars.len()
+    }
+
+    /// Whether there are no collars
+    pub fn is_empty(&self) -> bool {
+        self.collars.is_empty()
+    }
+
+    /// Serialize the address book for storage
+    ///
+    /// The format is a version byte followed by `name length, name, id (u16 little endian),
+    /// channel, protocol, limits, warning` for every collar, see [`Limits::encode`] and
+    /// [`Warning::encode`]. A collar without warning has all warning bytes set to zero.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.push(FORMAT_VERSION);
+        for collar in &self.collars {
+            bytes.push(collar.name.len() as u8);
+            bytes.extend_from_slice(collar.name.as_bytes());
+            bytes.extend_from_slice(&collar.id.to_le_bytes());
+            bytes.push(collar.channel as u8);
+            bytes.push(collar.protocol as u8);
+            bytes.extend_from_slice(&collar.limits.encode());
+            let warning = collar.warning.as_ref().map(Warning::encode);
+            bytes.extend_from_slice(&warning.unwrap_or_default());
+        }
+        bytes
+    }
+
+    /// Read an address book written by [`AddressBook::encode`]
+    pub fn decode(bytes: &[u8]) -> Result<Self, AddressBookError> {
+        let Some((&FORMAT_VERSION, mut bytes)) = bytes.split_first() else {
+            return Err(AddressBookError::Corrupted);
+        };
+        let mut book = Self::new();
+        while let Some((&length, rest)) = bytes.split_first() {
+            let length = length as usize;
+            let Some((name, rest)) = rest.split_at_checked(length) else {
+                return Err(AddressBookError::Corrupted);
+            };
+            let Some(([id_low, id_high, channel, protocol], rest)) = rest.split_first_chunk::<4>()
+            else {
+                return Err(AddressBookError::Corrupted);
+            };
+            let Some((limits, rest)) = rest.split_first_chunk::<ENCODED_LIMITS_LENGTH>() else {
+                return Err(AddressBookError::Corrupted);
+            };
+            let Some((warning, rest)) = rest.split_first_chunk::<ENCODED_WARNING_LENGTH>() else {
+                return Err(AddressBookError::Corrupted);
+            };
+            let warning = if *warning == [0; ENCODED_WARNING_LENGTH] {
+                None
+            } else {
+                Some(Warning::decode(warning).ok_or(AddressBookError::Corrupted)?)
+            };
+            let name = core::str::from_utf8(name).map_err(|_| AddressBookError::Corrupted)?;
+            let Some(limits) = Limits::decode(limits).filter(|_| is_valid_name(name)) else {
+                return Err(AddressBookError::Corrupted);
+            };
+            book.insert(Collar {
+                name: name.into(),
+                id: u16::from_le_bytes([*id_low, *id_high]),
+                channel: Channel::try_from(*channel).map_err(|_| AddressBookError::Corrupted)?,
+                protocol: ProtocolKind::try_from(*protocol)
+                    .map_err(|_| AddressBookError::Corrupted)?,
+                limits,
+                warning,
+            })?;
+            bytes = rest;
+        }
+        Ok(book)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::Action;
+    use alloc::vec;
+    use core::time::Duration;
+
+    fn collar(name: &str, id: u16) -> Collar {
+        Collar {
+            name: name.into(),
+            id,
+            channel: Channel::One,
+            protocol: ProtocolKind::Petrainer,
+            limits: Limits {
+                max_shock: 40,
+                ..Limits::NONE
+            },
+            warning: None,
+        }
+    }
+
+    /// Test adding, replacing and removing collars
+    #[test]
+    fn manages_collars() {
+        let mut book = AddressBook::new();
+        book.insert(collar("alice", 1)).unwrap();
+        book.insert(collar("bob", 2)).unwrap();
+        book.insert(collar("alice", 3)).unwrap();
+        assert_eq!(book.len(), 2);
+        assert_eq!(book.get("alice").unwrap().id, 3);
+
+        assert_eq!(book.remove("alice").unwrap().id, 3);
+        assert_eq!(book.remove("alice"), None);
+        assert_eq!(book.iter().map(|c| c.id).collect::<Vec<_>>(), [2]);
+
+        for id in 0..MAX_COLLARS as u16 - 1 {
+            book.insert(collar(&alloc::format!("c{}", id), id)).unwrap();
+        }
+        assert_eq!(book.insert(collar("full", 0)), Err(AddressBookError::Full));
+    }
+
+    /// Test that the stored format round trips and rejects garbage
+    #[test]
+    fn encodes_address_book() {
+        let mut book = AddressBook::new();
+        assert_eq!(AddressBook::decode(&book.encode()), Ok(book.clone()));
+        book.insert(collar("alice", 1234)).unwrap();
+        book.insert(Collar {
+            warning: Some(Warning {
+                action: Action::Beep,
+                intensity: 0,
+                duration: Duration::from_millis(500),
+                delay: Duration::from_secs(1),
+            }),
+            ..collar("bob_2", 65535)
+        })
+        .unwrap();
+        let bytes = book.encode();
+        assert_eq!(AddressBook::decode(&bytes), Ok(book.clone()));
+
+        assert_eq!(AddressBook::decode(&[]), Err(AddressBookError::Corrupted));
+        assert_eq!(
+            AddressBook::decode(&bytes[..bytes.len() - 1]),
+            Err(AddressBookError::Corrupted)
+        );
+        let mut invalid = vec![FORMAT_VERSION, 1, b' ', 0, 0, 0, 0];
+        invalid.extend_from_slice(&Limits::NONE.encode());
+        invalid.extend_from_slice(&[0; ENCODED_WARNING_LENGTH]);
+        assert_eq!(
+            AddressBook::decode(&invalid),
+            Err(AddressBookError::Corrupted)
+        );
+        invalid[2] = b'a';
+        assert!(AddressBook::decode(&invalid).is_ok());
+        invalid[0] = FORMAT_VERSION + 1;
+        assert_eq!(
+            AddressBook::decode(&invalid),
+            Err(AddressBookError::Corrupted)
+        );
+        let mut bytes = book.encode();
+        let length = bytes.len();
+        bytes[length - ENCODED_WARNING_LENGTH] = Action::Shock as u8;
+        assert_eq!(
+            AddressBook::decode(&bytes),
+            Err(AddressBookError::Corrupted)
+        );
+    }
+
+    /// Test which names are accepted
+    #[test]
+    fn validates_names() {
+        assert!(is_valid_name("alice"));
+        assert!(is_valid_name("Collar-2_b"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("a b"));
+        assert!(!is_valid_name("@alice"));
+        assert!(!is_valid_name("a_very_long_name"));
+    }
+}
@@ -0,0 +1,1823 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{num::IntErrorKind, time::Duration};
+
+use crate::{
+    collar,
+    limits::{Limits, MAX_COOLDOWN},
+    packet::{Action, Channel, Packet},
+    protocol::{InvalidProtocol, ProtocolKind},
+    queue::Repeat,
+    ramp::{Curve, Ramp, DEFAULT_PLATEAUS, MAX_PLATEAUS},
+    random::{RandomSettings, MAX_BURST, MAX_GAP, MAX_SESSION, MIN_GAP},
+    sequence::{Step, DEFAULT_STEP_DURATION, MAX_STEPS, MAX_STEP_DELAY},
+    warning::{
+        Warning, DEFAULT_WARNING_DELAY, DEFAULT_WARNING_DURATION, MAX_WARNING_DELAY,
+        MAX_WARNING_DURATION,
+    },
+};
+
+/// Help page listing all commands
+pub const HELP: &str = r#"Available commands:
+  help              : Print this help page
+  id 0-65535        : Set the id of this transmitter
+  channel 0-2       : Set the channel of this transmitter
+  intensity 0-99    : Set the intensity of the command
+  shock [0-99]      : Set the command type to zapping with the given intensity
+  vibrate [0-99]    : Set the command type to good vibrations with the given
+                      intensity
+  beep              : Set the command type to make beepy noises
+  light             : Transmit a light toggle command
+  transmit [1-65535]: Transmit the configured command the given amount (default 4),
+                      as long as it fits the duration limit (600s by default)
+  transmit --for 1500ms
+                    : Repeat the configured command for the given time (ms or s)
+                      up to the duration limit
+  send [id=0-65535] [ch=0-2] [protocol=name] [shock|vibrate [0-99]|beep|light]
+       [intensity=0-99] [x1-65535|for=1500ms]
+                    : Transmit once with the given settings, without changing
+                      the configured ones
+  stop              : Drop all queued transmissions and abort the current one
+  status [--json]   : Print the configuration and what the transmitter is doing
+  mode [text|json]  : Answer every command with a line of JSON instead of text
+  protocol [name]   : Set the protocol of the collar (caixianlin, petrainer)
+  sniff [on|off]    : Print the frames of remotes in range
+  rxpin 1-11        : Set the GPIO the 433 MHz receiver is connected to
+  learn [1-600]     : Copy id and channel from the next remote frame received
+                      within the given amount of seconds (default 30)
+  stoppin [1-11|off]: Set the GPIO of the emergency stop button
+  ble [on 0-999999|off]
+                    : Print or change whether the Bluetooth services are
+                      advertised (off by default), clients have to pair with
+                      the passkey before they can write
+  collar add name [id=0-65535] [ch=0-2] [protocol=name] [max=0-99]
+                    : Save a collar under a name, missing settings are taken
+                      from the current configuration, max limits shock and
+                      vibrate and needs unlocking
+  collar remove name: Forget a saved collar
+  collar [list]     : List the saved collars
+  collar use name   : Configure id, channel and protocol of a saved collar
+  collar warn name off|beep|vibrate 0-99 [for=500ms] [delay=1s]
+                    : Precede every shock to a saved collar with a beep or
+                      vibrate burst and a pause, turning it off needs
+                      unlocking
+  shock 20 @name    : Transmit to a saved collar without changing the
+                      configuration, works with shock, vibrate, beep, light,
+                      transmit and send
+  limits [@name] [shock=0-99] [vibrate=0-99] [duration=1500ms] [cooldown=5s]
+                    : Print or change the safety limits, globally or of a
+                      saved collar, changes need unlocking, the duration is
+                      600s unless changed
+  ramp shock|vibrate 0-99 0-99 5s [linear|exponential|stepped [2-20]]
+                    : Move the intensity from one value to the other over the
+                      given time, works with @name
+  random start [actions=vibrate,beep] [intensity=10-30] [gap=10s-60s]
+         [for=500ms] [session=600s] [seed=number]
+                    : Transmit random actions at random times within the
+                      bounds until the session ends, works with @name
+  random [stop]     : Print or stop the random session
+  macro define name step...
+                    : Save a sequence of steps like vibrate 30 500ms pause 1s
+                      vibrate 60 500ms beep, the duration defaults to 500ms
+  macro run name    : Transmit a saved sequence, works with @name
+  macro [list]      : List the saved sequences
+  macro delete name : Forget a saved sequence
+  unlock            : Allow changing the limits for the next 60 seconds
+  lock              : Lock the limits again
+  "#;
+
+/// Longest time a single transmit command may last
+pub const MAX_TRANSMIT_DURATION: Duration = Duration::from_secs(600);
+
+/// Amount of frames sent by a transmit without an amount
+pub(crate) const DEFAULT_REPEAT: Repeat = Repeat::Count(4);
+
+/// Commands understood by the serial shell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Print the help page
+    Help,
+    /// Set the id of the transmitter
+    Id(u16),
+    /// Set the channel of the transmitter
+    Channel(Channel),
+    /// Set the intensity
+    Intensity(u8),
+    /// Set the action to vibrate, optionally with a new intensity
+    Vibrate(Option<u8>),
+    /// Set the action to shock, optionally with a new intensity
+    Shock(Option<u8>),
+    /// Set the action to beep
+    Beep,
+    /// Set the action to light
+    Light,
+    /// Set the protocol, or list the protocols if none is given
+    Protocol(Option<ProtocolKind>),
+    /// Enable, disable or toggle sniffing
+    Sniff(Option<bool>),
+    /// Set the GPIO of the receiver
+    RxPin(u8),
+    /// Learn the identity of a remote within the given amount of seconds
+    Learn(u16),
+    /// Transmit the configured packet repeatedly
+    Transmit(Repeat),
+    /// Transmit a packet with some settings overridden, without storing them
+    Send(Overrides, Repeat),
+    /// Drop all queued transmissions
+    Stop,
+    /// Print the configuration and activity, optionally as JSON
+    Status { json: bool },
+    /// Set the output format, or print it if none is given
+    Mode(Option<Mode>),
+    /// Set or disable the GPIO of the emergency stop button
+    StopPin(Option<u8>),
+    /// Advertise the Bluetooth services with the passkey for pairing, stop advertising if there
+    /// is none, or print the state if nothing is given
+    Bluetooth(Option<Option<u32>>),
+    /// Manage the address book
+    Collar(CollarCommand),
+    /// Print the limits, or change them if there are changes, of a collar or globally
+    Limits {
+        collar: Option<String>,
+        changes: LimitChanges,
+    },
+    /// Move the intensity over time, to a collar or the configured identity
+    Ramp { ramp: Ramp, collar: Option<String> },
+    /// Start, stop or print the random session
+    Random(RandomCommand),
+    /// Manage and run saved sequences
+    Macro(MacroCommand),
+    /// Allow changing the limits for a while
+    Unlock,
+    /// Forbid changing the limits
+    Lock,
+}
+
+/// Subcommands of the address book
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CollarCommand {
+    /// Save a collar, settings that are not given are taken from the configuration
+    Add {
+        name: String,
+        id: Option<u16>,
+        channel: Option<Channel>,
+        protocol: Option<ProtocolKind>,
+        max_intensity: Option<u8>,
+    },
+    /// Forget a collar
+    Remove(String),
+    /// List all collars
+    List,
+    /// Configure the settings of a collar
+    Use(String),
+    /// Set or clear the warning that precedes shocks to a collar
+    Warn {
+        name: String,
+        warning: Option<Warning>,
+    },
+}
+
+/// Subcommands of the random session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RandomCommand {
+    /// Start a session to a collar or the configured identity
+    Start {
+        settings: RandomSettings,
+        collar: Option<String>,
+    },
+    /// Stop the session
+    Stop,
+    /// Print the session
+    Show,
+}
+
+/// Subcommands of the saved sequences
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MacroCommand {
+    /// Save a sequence under a name
+    Define { name: String, steps: Vec<Step> },
+    /// Transmit a saved sequence to a collar or the configured identity
+    Run {
+        name: String,
+        collar: Option<String>,
+    },
+    /// List all sequences
+    List,
+    /// Forget a sequence
+    Delete(String),
+}
+
+/// Output format of the shell
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Mode {
+    /// Human readable text
+    #[default]
+    Text = 0,
+    /// One JSON object per line
+    Json = 1,
+}
+
+impl Mode {
+    /// Name of the mode
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Text => "text",
+            Mode::Json => "json",
+        }
+    }
+}
+
+/// Settings that replace the configured ones for a single transmission
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Overrides {
+    /// Saved collar whose settings are used instead of the configured ones
+    pub collar: Option<String>,
+    /// Shocker ID
+    pub id: Option<u16>,
+    /// Channel to transmit on
+    pub channel: Option<Channel>,
+    /// Protocol of the collar
+    pub protocol: Option<ProtocolKind>,
+    /// Action to perform
+    pub action: Option<Action>,
+    /// Intensity of the action
+    pub intensity: Option<u8>,
+}
+
+impl Overrides {
+    /// Replace the fields of a packet that are overridden
+    ///
+    /// The collar is not resolved here, its settings have to be applied to the packet before.
+    pub fn apply(&self, packet: &Packet) -> Packet {
+        Packet {
+            id: self.id.unwrap_or(packet.id),
+            channel: self.channel.unwrap_or(packet.channel),
+            action: self.action.unwrap_or(packet.action),
+            intensity: self.intensity.unwrap_or(packet.intensity),
+        }
+    }
+}
+
+/// Limits that should be changed, the others are kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LimitChanges {
+    /// Highest shock intensity
+    pub max_shock: Option<u8>,
+    /// Highest vibrate intensity
+    pub max_vibrate: Option<u8>,
+    /// Longest time a single transmission may last
+    pub max_duration: Option<Duration>,
+    /// Shortest time between shocks to the same collar
+    pub cooldown: Option<Duration>,
+}
+
+impl LimitChanges {
+    /// Whether nothing should be changed
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Replace the limits that are changed
+    pub fn apply(&self, limits: &Limits) -> Limits {
+        Limits {
+            max_shock: self.max_shock.unwrap_or(limits.max_shock),
+            max_vibrate: self.max_vibrate.unwrap_or(limits.max_vibrate),
+            max_duration: self.max_duration.unwrap_or(limits.max_duration),
+            cooldown: self.cooldown.unwrap_or(limits.cooldown),
+        }
+    }
+}
+
+/// Errors that can occur while parsing a command
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseError {
+    /// The line contains no command
+    #[error("Empty command")]
+    Empty,
+    /// The command is not known
+    #[error("Unknown command {0}")]
+    UnknownCommand(String),
+    /// A quoted string is not closed
+    #[error("Missing closing quote")]
+    UnterminatedQuote,
+    /// A required argument is missing
+    #[error("Missing {name}")]
+    MissingArgument { name: &'static str },
+    /// There are more arguments than the command takes
+    #[error("Unexpected argument {0}")]
+    UnexpectedArgument(String),
+    /// A numeric argument is not a number
+    #[error("{name} must be a number, got {got}")]
+    InvalidNumber { name: &'static str, got: String },
+    /// A numeric argument is out of range
+    #[error("{name} must be between {min} and {max}")]
+    OutOfRange {
+        name: &'static str,
+        min: i32,
+        max: i32,
+    },
+    /// An argument is not one of the expected values
+    #[error("Expected {expected}, got {got}")]
+    InvalidArgument { expected: &'static str, got: String },
+    /// The protocol is not known
+    #[error(transparent)]
+    InvalidProtocol(#[from] InvalidProtocol),
+    /// The GPIO is wired to the flash or the console
+    #[error("GPIO {0} is reserved for the flash or the console")]
+    ReservedPin(u8),
+}
+
+impl ParseError {
+    /// Stable identifier of the error for machine readable output
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::Empty => "empty",
+            ParseError::UnknownCommand(_) => "unknown_command",
+            ParseError::UnterminatedQuote => "unterminated_quote",
+            ParseError::MissingArgument { .. } => "missing_argument",
+            ParseError::UnexpectedArgument(_) => "unexpected_argument",
+            ParseError::InvalidNumber { .. } => "invalid_number",
+            ParseError::OutOfRange { .. } => "out_of_range",
+            ParseError::InvalidArgument { .. } => "invalid_argument",
+            ParseError::InvalidProtocol(_) => "invalid_protocol",
+            ParseError::ReservedPin(_) => "reserved_pin",
+        }
+    }
+}
+
+/// Split a line into whitespace separated tokens
+///
+/// Single or double quotes group words into one token and a backslash escapes the next
+/// character, so `"a b"` and `a\ b` are both the single token `a b`.
+pub fn tokenize(line: &str) -> Result<Vec<String>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    // a token was started, even if it is still empty like `""`
+    let mut in_token = false;
+    let mut quote = None;
+    let mut chars = line.chars();
+
+    while let Some(char) = chars.next() {
+        match (char, quote) {
+            ('\\', _) => {
+                // a trailing backslash is kept as is
+                token.push(chars.next().unwrap_or('\\'));
+                in_token = true;
+            }
+            (char, Some(open)) if char == open => quote = None,
+            (char, Some(_)) => token.push(char),
+            ('"' | '\'', None) => {
+                quote = Some(char);
+                in_token = true;
+            }
+            (char, None) if char.is_whitespace() => {
+                if in_token {
+                    tokens.push(core::mem::take(&mut token));
+                    in_token = false;
+                }
+            }
+            (char, None) => {
+                token.push(char);
+                in_token = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(ParseError::UnterminatedQuote);
+    }
+    if in_token {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Whether a GPIO of the ESP32-C3 can be used for the receiver or the stop button
+///
+/// GPIO 0 drives the transmitter, 12 to 17 connect the SPI flash, 18 and 19 are the USB console
+/// and 20 and 21 the UART console. Using any of them keeps the firmware from booting or from
+/// being configured again.
+pub fn is_usable_gpio(pin: u8) -> bool {
+    (1..=11).contains(&pin)
+}
+
+/// Check that a number is within the given range and convert it
+fn in_range<T: TryFrom<i32>>(
+    name: &'static str,
+    value: i32,
+    min: i32,
+    max: i32,
+) -> Result<T, ParseError> {
+    if !(min..=max).contains(&value) {
+        return Err(ParseError::OutOfRange { name, min, max });
+    }
+    T::try_from(value).map_err(|_| ParseError::OutOfRange { name, min, max })
+}
+
+/// Parse a number and check that it is within the given range
+fn number<T: TryFrom<i32>>(
+    name: &'static str,
+    token: &str,
+    min: i32,
+    max: i32,
+) -> Result<T, ParseError> {
+    let value = match token.parse::<i32>() {
+        Ok(value) => value,
+        // too many digits to be in range anyway
+        Err(error)
+            if matches!(
+                error.kind(),
+                IntErrorKind::PosOverflow | IntErrorKind::NegOverflow
+            ) =>
+        {
+            return Err(ParseError::OutOfRange { name, min, max })
+        }
+        Err(_) => {
+            return Err(ParseError::InvalidNumber {
+                name,
+                got: token.to_string(),
+            })
+        }
+    };
+    in_range(name, value, min, max)
+}
+
+/// Parse a duration like `1500ms`, `2s` or `1.5s`
+pub fn parse_duration(duration: &str) -> Option<Duration> {
+    // parse alone would also take a sign like +5ms
+    let digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if let Some(millis) = duration.strip_suffix("ms") {
+        if !digits(millis) {
+            return None;
+        }
+        return millis.parse().ok().map(Duration::from_millis);
+    }
+    // whole seconds and milliseconds are parsed separately, floats would turn 0.251s into 250ms
+    let seconds = duration.strip_suffix('s')?;
+    let (whole, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    if whole.len() + fraction.len() == 0
+        || fraction.len() > 3
+        || !digits(whole)
+        || !digits(fraction)
+    {
+        return None;
+    }
+    let whole: u64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().ok()?
+    };
+    // pad the fraction to milliseconds, so .5 is 500
+    let millis = fraction
+        .bytes()
+        .chain(core::iter::repeat(b'0'))
+        .take(3)
+        .fold(0, |millis, digit| millis * 10 + (digit - b'0') as u64);
+    let millis = whole.checked_mul(1000)?.checked_add(millis)?;
+    Some(Duration::from_millis(millis))
+}
+
+/// Parse the duration of a timed transmission
+fn transmit_duration(token: &str) -> Result<Duration, ParseError> {
+    let Some(duration) = parse_duration(token) else {
+        return Err(ParseError::InvalidArgument {
+            expected: "a duration like 1500ms or 2s",
+            got: token.to_string(),
+        });
+    };
+    if duration.is_zero() || duration > MAX_TRANSMIT_DURATION {
+        return Err(ParseError::OutOfRange {
+            name: "Duration in ms",
+            min: 1,
+            max: MAX_TRANSMIT_DURATION.as_millis() as i32,
+        });
+    }
+    Ok(duration)
+}
+
+/// Parse the cooldown between shocks
+fn cooldown(token: &str) -> Result<Duration, ParseError> {
+    let Some(cooldown) = parse_duration(token) else {
+        return Err(ParseError::InvalidArgument {
+            expected: "a duration like 1500ms or 2s",
+            got: token.to_string(),
+        });
+    };
+    if cooldown > MAX_COOLDOWN {
+        return Err(ParseError::OutOfRange {
+            name: "Cooldown in ms",
+            min: 0,
+            max: MAX_COOLDOWN.as_millis() as i32,
+        });
+    }
+    Ok(cooldown)
+}
+
+/// Parse a duration that has to be within the given range
+fn bounded_duration(
+    name: &'static str,
+    token: &str,
+    min: Duration,
+    max: Duration,
+) -> Result<Duration, ParseError> {
+    let Some(duration) = parse_duration(token) else {
+        return Err(ParseError::InvalidArgument {
+            expected: "a duration like 1500ms or 2s",
+            got: token.to_string(),
+        });
+    };
+    if !(min..=max).contains(&duration) {
+        return Err(ParseError::OutOfRange {
+            name,
+            min: min.as_millis() as i32,
+            max: max.as_millis() as i32,
+        });
+    }
+    Ok(duration)
+}
+
+/// Parse the name of a collar
+fn collar_name(token: &str) -> Result<String, ParseError> {
+    if !collar::is_valid_name(token) {
+        return Err(ParseError::InvalidArgument {
+            expected: "a name of up to 15 letters, digits, - or _",
+            got: token.to_string(),
+        });
+    }
+    Ok(token.to_string())
+}
+
+/// Parse an action name
+fn action(name: &str) -> Option<Action> {
+    match name {
+        "shock" | "s" => Some(Action::Shock),
+        "vibrate" | "v" => Some(Action::Vibrate),
+        "beep" | "b" => Some(Action::Beep),
+        "light" | "l" => Some(Action::Light),
+        _ => None,
+    }
+}
+
+/// Arguments of a command that are consumed while parsing
+struct Arguments<'a> {
+    tokens: &'a [String],
+}
+
+impl<'a> Arguments<'a> {
+    /// Take the next argument
+    fn next(&mut self) -> Option<&'a str> {
+        let (first, rest) = self.tokens.split_first()?;
+        self.tokens = rest;
+        Some(first)
+    }
+
+    /// Look at the next argument without taking it
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.first().map(String::as_str)
+    }
+
+    /// Take the next argument as a number if there is one
+    fn optional<T: TryFrom<i32>>(
+        &mut self,
+        name: &'static str,
+        min: i32,
+        max: i32,
+    ) -> Result<Option<T>, ParseError> {
+        self.next()
+            .map(|token| number(name, token, min, max))
+            .transpose()
+    }
+
+    /// Take the next argument as a number
+    fn required<T: TryFrom<i32>>(
+        &mut self,
+        name: &'static str,
+        min: i32,
+        max: i32,
+    ) -> Result<T, ParseError> {
+        self.optional(name, min, max)?
+            .ok_or(ParseError::MissingArgument { name })
+    }
+
+    /// Take the next argument as a GPIO that is free to use
+    fn gpio(&mut self, name: &'static str) -> Result<u8, ParseError> {
+        let pin = self.required(name, 1, 21)?;
+        if !is_usable_gpio(pin) {
+            return Err(ParseError::ReservedPin(pin));
+        }
+        Ok(pin)
+    }
+
+    /// Make sure all arguments were consumed
+    fn finish(&self) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(token) => Err(ParseError::UnexpectedArgument(token.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parse the arguments of a send command
+fn parse_send(arguments: &mut Arguments) -> Result<Command, ParseError> {
+    let mut overrides = Overrides::default();
+    let mut repeat = DEFAULT_REPEAT;
+
+    while let Some(token) = arguments.next() {
+        if let Some(action) = action(token) {
+            overrides.action = Some(action);
+            // shock and vibrate may be followed by an intensity
+            let takes_intensity = matches!(action, Action::Shock | Action::Vibrate);
+            if takes_intensity
+                && arguments
+                    .peek()
+                    .is_some_and(|token| token.parse::<i32>().is_ok())
+            {
+                overrides.intensity = arguments.optional("Intensity", 0, 99)?;
+            }
+            continue;
+        }
+        if let Some(amount) = token.strip_prefix('x') {
+            repeat = Repeat::Count(number("Amount", amount, 1, 65535)?);
+            continue;
+        }
+        let Some((key, value)) = token.split_once('=') else {
+            return Err(ParseError::UnexpectedArgument(token.to_string()));
+        };
+        match key {
+            "id" => overrides.id = Some(number("ID", value, 0, 65535)?),
+            "ch" | "channel" => {
+                let channel: u8 = number("Channel", value, 0, 2)?;
+                overrides.channel = Some(Channel::try_from(channel).expect("channel is in range"));
+            }
+            "i" | "intensity" => overrides.intensity = Some(number("Intensity", value, 0, 99)?),
+            "p" | "protocol" => overrides.protocol = Some(value.parse()?),
+            "for" => repeat = Repeat::For(transmit_duration(value)?),
+            _ => return Err(ParseError::UnexpectedArgument(token.to_string())),
+        }
+    }
+
+    Ok(Command::Send(overrides, repeat))
+}
+
+/// Parse the arguments of a collar command
+fn parse_collar(arguments: &mut Arguments) -> Result<CollarCommand, ParseError> {
+    let subcommand = match arguments.next() {
+        None | Some("list") => CollarCommand::List,
+        Some("remove") => CollarCommand::Remove(collar_name(
+            arguments
+                .next()
+                .ok_or(ParseError::MissingArgument { name: "Name" })?,
+        )?),
+        Some("use") => CollarCommand::Use(collar_name(
+            arguments
+                .next()
+                .ok_or(ParseError::MissingArgument { name: "Name" })?,
+        )?),
+        Some("add") => {
+            let name = collar_name(
+                arguments
+                    .next()
+                    .ok_or(ParseError::MissingArgument { name: "Name" })?,
+            )?;
+            let (mut id, mut channel, mut protocol, mut max_intensity) = (None, None, None, None);
+            while let Some(token) = arguments.next() {
+                let Some((key, value)) = token.split_once('=') else {
+                    return Err(ParseError::UnexpectedArgument(token.to_string()));
+                };
+                match key {
+                    "id" => id = Some(number("ID", value, 0, 65535)?),
+                    "ch" | "channel" => {
+                        let value: u8 = number("Channel", value, 0, 2)?;
+                        channel = Some(Channel::try_from(value).expect("channel is in range"));
+                    }
+                    "p" | "protocol" => protocol = Some(value.parse()?),
+                    "max" => max_intensity = Some(number("Maximum intensity", value, 0, 99)?),
+                    _ => return Err(ParseError::UnexpectedArgument(token.to_string())),
+                }
+            }
+            CollarCommand::Add {
+                name,
+                id,
+                channel,
+                protocol,
+                max_intensity,
+            }
+        }
+        Some("warn") => {
+            let name = collar_name(
+                arguments
+                    .next()
+                    .ok_or(ParseError::MissingArgument { name: "Name" })?,
+            )?;
+            CollarCommand::Warn {
+                name,
+                warning: parse_warning(arguments)?,
+            }
+        }
+        Some(argument) => {
+            return Err(ParseError::InvalidArgument {
+                expected: "add, remove, list, use or warn",
+                got: argument.to_string(),
+            })
+        }
+    };
+    Ok(subcommand)
+}
+
+/// Parse the warning of a collar warn command, `None` if it is turned off
+fn parse_warning(arguments: &mut Arguments) -> Result<Option<Warning>, ParseError> {
+    let action = match arguments.next() {
+        Some("off") => return Ok(None),
+        Some("beep" | "b") => Action::Beep,
+        Some("vibrate" | "v") => Action::Vibrate,
+        Some(argument) => {
+            return Err(ParseError::InvalidArgument {
+                expected: "off, beep or vibrate",
+                got: argument.to_string(),
+            })
+        }
+        None => return Err(ParseError::MissingArgument { name: "Warning" }),
+    };
+    let mut warning = Warning {
+        action,
+        intensity: match action {
+            Action::Vibrate => arguments.required("Intensity", 0, 99)?,
+            _ => 0,
+        },
+        duration: DEFAULT_WARNING_DURATION,
+        delay: DEFAULT_WARNING_DELAY,
+    };
+    while let Some(token) = arguments.next() {
+        let Some((key, value)) = token.split_once('=') else {
+            return Err(ParseError::UnexpectedArgument(token.to_string()));
+        };
+        match key {
+            "for" => {
+                warning.duration = bounded_duration(
+                    "Warning duration in ms",
+                    value,
+                    Duration::from_millis(1),
+                    MAX_WARNING_DURATION,
+                )?
+            }
+            "delay" => {
+                warning.delay = bounded_duration(
+                    "Warning delay in ms",
+                    value,
+                    Duration::ZERO,
+                    MAX_WARNING_DELAY,
+                )?
+            }
+            _ => return Err(ParseError::UnexpectedArgument(token.to_string())),
+        }
+    }
+    Ok(Some(warning))
+}
+
+/// Parse the arguments of a ramp command
+fn parse_ramp(arguments: &mut Arguments) -> Result<Ramp, ParseError> {
+    let action = match arguments.next() {
+        Some("shock" | "s") => Action::Shock,
+        Some("vibrate" | "v") => Action::Vibrate,
+        Some(argument) => {
+            return Err(ParseError::InvalidArgument {
+                expected: "shock or vibrate",
+                got: argument.to_string(),
+            })
+        }
+        None => return Err(ParseError::MissingArgument { name: "Action" }),
+    };
+    let from = arguments.required("Start intensity", 0, 99)?;
+    let to = arguments.required("End intensity", 0, 99)?;
+    let duration = transmit_duration(
+        arguments
+            .next()
+            .ok_or(ParseError::MissingArgument { name: "Duration" })?,
+    )?;
+    let curve = match arguments.next() {
+        None | Some("linear") => Curve::Linear,
+        Some("exponential" | "exp") => Curve::Exponential,
+        Some("stepped") => Curve::Stepped(
+            arguments
+                .optional("Amount of steps", 2, MAX_PLATEAUS as i32)?
+                .unwrap_or(DEFAULT_PLATEAUS),
+        ),
+        Some(argument) => {
+            return Err(ParseError::InvalidArgument {
+                expected: "linear, exponential or stepped",
+                got: argument.to_string(),
+            })
+        }
+    };
+    Ok(Ramp {
+        action,
+        from,
+        to,
+        duration,
+        curve,
+    })
+}
+
+/// Split a range like `10-30`, a single value is both ends
+fn split_range(value: &str) -> (&str, &str) {
+    value.split_once('-').unwrap_or((value, value))
+}
+
+/// Parse the arguments of a random command
+fn parse_random(arguments: &mut Arguments) -> Result<RandomCommand, ParseError> {
+    match arguments.next() {
+        None => return Ok(RandomCommand::Show),
+        Some("stop") => return Ok(RandomCommand::Stop),
+        Some("start") => {}
+        Some(argument) => {
+            return Err(ParseError::InvalidArgument {
+                expected: "start or stop",
+                got: argument.to_string(),
+            })
+        }
+    }
+
+    let mut settings = RandomSettings::default();
+    while let Some(token) = arguments.next() {
+        let Some((key, value)) = token.split_once('=') else {
+            return Err(ParseError::UnexpectedArgument(token.to_string()));
+        };
+        match key {
+            "actions" => {
+                settings.actions.clear();
+                for name in value.split(',') {
+                    match action(name) {
+                        Some(action @ (Action::Shock | Action::Vibrate | Action::Beep)) => {
+                            if !settings.actions.contains(&action) {
+                                settings.actions.push(action);
+                            }
+                        }
+                        _ => {
+                            return Err(ParseError::InvalidArgument {
+                                expected: "shock, vibrate or beep",
+                                got: name.to_string(),
+                            })
+                        }
+                    }
+                }
+            }
+            "i" | "intensity" => {
+                let (min, max) = split_range(value);
+                settings.min_intensity = number("Intensity", min, 0, 99)?;
+                settings.max_intensity =
+                    number("Intensity", max, settings.min_intensity as i32, 99)?;
+            }
+            "gap" => {
+                let (min, max) = split_range(value);
+                settings.min_gap = bounded_duration("Gap in ms", min, MIN_GAP, MAX_GAP)?;
+                settings.max_gap = bounded_duration("Gap in ms", max, settings.min_gap, MAX_GAP)?;
+            }
+            "for" => {
+                settings.burst =
+                    bounded_duration("Duration in ms", value, Duration::from_millis(1), MAX_BURST)?
+            }
+            "session" => {
+                settings.session =
+                    bounded_duration("Session in ms", value, Duration::from_secs(1), MAX_SESSION)?
+            }
+            "seed" => {
+                settings.seed = Some(value.parse().map_err(|_| ParseError::InvalidNumber {
+                    name: "Seed",
+                    got: value.to_string(),
+                })?)
+            }
+            _ => return Err(ParseError::UnexpectedArgument(token.to_string())),
+        }
+    }
+    Ok(RandomCommand::Start {
+        settings,
+        collar: None,
+    })
+}
+
+/// Parse the arguments of a macro command
+fn parse_macro(arguments: &mut Arguments) -> Result<MacroCommand, ParseError> {
+    let name = |arguments: &mut Arguments| {
+        collar_name(
+            arguments
+                .next()
+                .ok_or(ParseError::MissingArgument { name: "Name" })?,
+        )
+    };
+    let subcommand = match arguments.next() {
+        None | Some("list") => MacroCommand::List,
+        Some("run") => MacroCommand::Run {
+            name: name(arguments)?,
+            collar: None,
+        },
+        Some("delete") => MacroCommand::Delete(name(arguments)?),
+        Some("define") => MacroCommand::Define {
+            name: name(arguments)?,
+            steps: parse_steps(arguments)?,
+        },
+        Some(argument) => {
+            return Err(ParseError::InvalidArgument {
+                expected: "define, run, list or delete",
+                got: argument.to_string(),
+            })
+        }
+    };
+    Ok(subcommand)
+}
+
+/// Parse the steps of a sequence like `vibrate 30 500ms pause 1s beep`
+fn parse_steps(arguments: &mut Arguments) -> Result<Vec<Step>, ParseError> {
+    let mut steps: Vec<Step> = Vec::new();
+    while let Some(token) = arguments.next() {
+        if token == "pause" {
+            let Some(step) = steps.last_mut() else {
+                return Err(ParseError::UnexpectedArgument(token.to_string()));
+            };
+            let pause = arguments
+                .next()
+                .ok_or(ParseError::MissingArgument { name: "Pause" })?;
+            step.delay = bounded_duration("Pause in ms", pause, Duration::ZERO, MAX_STEP_DELAY)?;
+            continue;
+        }
+        let Some(action) = action(token) else {
+            return Err(ParseError::InvalidArgument {
+                expected: "an action or pause",
+                got: token.to_string(),
+            });
+        };
+        let intensity = match action {
+            Action::Shock | Action::Vibrate => arguments.required("Intensity", 0, 99)?,
+            Action::Beep | Action::Light => 0,
+        };
+        let duration = match arguments.peek() {
+            Some(token) if parse_duration(token).is_some() => {
+                arguments.next();
+                transmit_duration(token)?
+            }
+            _ => DEFAULT_STEP_DURATION,
+        };
+        steps.push(Step {
+            action,
+            intensity,
+            duration,
+            delay: Duration::ZERO,
+        });
+    }
+    if steps.is_empty() {
+        return Err(ParseError::MissingArgument { name: "Steps" });
+    }
+    if steps.len() > MAX_STEPS {
+        return Err(ParseError::OutOfRange {
+            name: "Amount of steps",
+            min: 1,
+            max: MAX_STEPS as i32,
+        });
+    }
+    Ok(steps)
+}
+
+/// Parse the arguments of a limits command
+fn parse_limits(arguments: &mut Arguments) -> Result<LimitChanges, ParseError> {
+    let mut changes = LimitChanges::default();
+    while let Some(token) = arguments.next() {
+        let Some((key, value)) = token.split_once('=') else {
+            return Err(ParseError::UnexpectedArgument(token.to_string()));
+        };
+        match key {
+            "shock" => changes.max_shock = Some(number("Shock limit", value, 0, 99)?),
+            "vibrate" => changes.max_vibrate = Some(number("Vibrate limit", value, 0, 99)?),
+            "duration" => changes.max_duration = Some(transmit_duration(value)?),
+            "cooldown" => changes.cooldown = Some(cooldown(value)?),
+            _ => return Err(ParseError::UnexpectedArgument(token.to_string())),
+        }
+    }
+    Ok(changes)
+}
+
+/// Turn a command into a transmission to the given collar
+///
+/// Actions and transmits are sent right away instead of changing the configuration, so
+/// `shock 20 @alice` shocks alice once. Limits apply to the collar instead of globally.
+fn target(command: Command, collar: String) -> Result<Command, ParseError> {
+    let overrides = |action, intensity| Overrides {
+        collar: Some(collar.clone()),
+        action: Some(action),
+        intensity,
+        ..Overrides::default()
+    };
+    let command = match command {
+        Command::Send(overrides, repeat) => Command::Send(
+            Overrides {
+                collar: Some(collar),
+                ..overrides
+            },
+            repeat,
+        ),
+        Command::Transmit(repeat) => Command::Send(
+            Overrides {
+                collar: Some(collar),
+                ..Overrides::default()
+            },
+            repeat,
+        ),
+        Command::Shock(intensity) => {
+            Command::Send(overrides(Action::Shock, intensity), DEFAULT_REPEAT)
+        }
+        Command::Vibrate(intensity) => {
+            Command::Send(overrides(Action::Vibrate, intensity), DEFAULT_REPEAT)
+        }
+        Command::Beep => Command::Send(overrides(Action::Beep, None), DEFAULT_REPEAT),
+        Command::Light => Command::Send(overrides(Action::Light, None), DEFAULT_REPEAT),
+        Command::Limits {
+            collar: None,
+            changes,
+        } => Command::Limits {
+            collar: Some(collar),
+            changes,
+        },
+        Command::Ramp { ramp, collar: None } => Command::Ramp {
+            ramp,
+            collar: Some(collar),
+        },
+        Command::Random(RandomCommand::Start {
+            settings,
+            collar: None,
+        }) => Command::Random(RandomCommand::Start {
+            settings,
+            collar: Some(collar),
+        }),
+        Command::Macro(MacroCommand::Run { name, collar: None }) => {
+            Command::Macro(MacroCommand::Run {
+                name,
+                collar: Some(collar),
+            })
+        }
+        _ => {
+            return Err(ParseError::UnexpectedArgument(alloc::format!(
+                "@{}", collar
+            )))
+        }
+    };
+    Ok(command)
+}
+
+/// Parse a command line like `<command> [arguments...]`
+///
+/// Missing optional arguments fall back to their defaults, while arguments that are present but
+/// invalid and arguments the command does not take are errors.
+pub fn parse(line: &str) -> Result<Command, ParseError> {
+    let mut tokens = tokenize(line)?;
+
+    // a collar to send to may be given anywhere after the command
+    let mut collar = None;
+    if let Some(index) = tokens
+        .iter()
+        .skip(1)
+        .position(|token| token.starts_with('@'))
+    {
+        let token = tokens.remove(index + 1);
+        collar = Some(collar_name(&token[1..])?);
+    }
+    if let Some(token) = tokens.iter().skip(1).find(|token| token.starts_with('@')) {
+        return Err(ParseError::UnexpectedArgument(token.clone()));
+    }
+
+    let Some((name, tokens)) = tokens.split_first() else {
+        return Err(ParseError::Empty);
+    };
+    let mut arguments = Arguments { tokens };
+
+    let command = match name.as_str() {
+        "help" => Command::Help,
+        "id" => Command::Id(arguments.required("ID", 0, 65535)?),
+        "channel" | "c" => {
+            let channel: u8 = arguments.required("Channel", 0, 2)?;
+            Command::Channel(Channel::try_from(channel).expect("channel is in range"))
+        }
+        "intensity" | "i" => Command::Intensity(arguments.required("Intensity", 0, 99)?),
+        "vibrate" | "v" => Command::Vibrate(arguments.optional("Intensity", 0, 99)?),
+        "shock" | "s" => Command::Shock(arguments.optional("Intensity", 0, 99)?),
+        "beep" | "b" => Command::Beep,
+        "light" | "l" => Command::Light,
+        "protocol" | "p" => match arguments.next() {
+            Some(argument) => Command::Protocol(Some(argument.parse()?)),
+            None => Command::Protocol(None),
+        },
+        "sniff" => Command::Sniff(match arguments.next() {
+            None => None,
+            Some("on") => Some(true),
+            Some("off") => Some(false),
+            Some(argument) => {
+                return Err(ParseError::InvalidArgument {
+                    expected: "on or off",
+                    got: argument.to_string(),
+                })
+            }
+        }),
+        "rxpin" => Command::RxPin(arguments.gpio("Receiver GPIO")?),
+        // default timeout is 30 seconds
+        "learn" => Command::Learn(arguments.optional("Timeout", 1, 600)?.unwrap_or(30)),
+        "transmit" | "t" if arguments.peek() == Some("--for") => {
+            arguments.next();
+            let duration = arguments
+                .next()
+                .ok_or(ParseError::MissingArgument { name: "Duration" })?;
+            Command::Transmit(Repeat::For(transmit_duration(duration)?))
+        }
+        "transmit" | "t" => Command::Transmit(
+            arguments
+                .optional("Amount", 0, 65535)?
+                .map_or(DEFAULT_REPEAT, Repeat::Count),
+        ),
+        "send" => parse_send(&mut arguments)?,
+        "stop" => Command::Stop,
+        "mode" => Command::Mode(match arguments.next() {
+            None => None,
+            Some("text") => Some(Mode::Text),
+            Some("json") => Some(Mode::Json),
+            Some(argument) => {
+                return Err(ParseError::InvalidArgument {
+                    expected: "text or json",
+                    got: argument.to_string(),
+                })
+            }
+        }),
+        "status" => Command::Status {
+            json: match arguments.next() {
+                None => false,
+                Some("--json") => true,
+                Some(argument) => {
+                    return Err(ParseError::InvalidArgument {
+                        expected: "--json",
+                        got: argument.to_string(),
+                    })
+                }
+            },
+        },
+        "ble" => Command::Bluetooth(match arguments.next() {
+            None => None,
+            Some("off") => Some(None),
+            Some("on") => Some(Some(arguments.required("Passkey", 0, 999999)?)),
+            Some(argument) => {
+                return Err(ParseError::InvalidArgument {
+                    expected: "on or off",
+                    got: argument.to_string(),
+                })
+            }
+        }),
+        "stoppin" => Command::StopPin(match arguments.peek() {
+            Some("off") => {
+                arguments.next();
+                None
+            }
+            _ => Some(arguments.gpio("Stop button GPIO")?),
+        }),
+        "collar" => Command::Collar(parse_collar(&mut arguments)?),
+        "limits" => Command::Limits {
+            collar: None,
+            changes: parse_limits(&mut arguments)?,
+        },
+        "ramp" => Command::Ramp {
+            ramp: parse_ramp(&mut arguments)?,
+            collar: None,
+        },
+        "random" => Command::Random(parse_random(&mut arguments)?),
+        "macro" => Command::Macro(parse_macro(&mut arguments)?),
+        "unlock" => Command::Unlock,
+        "lock" => Command::Lock,
+        _ => return Err(ParseError::UnknownCommand(name.to_string())),
+    };
+    arguments.finish()?;
+    match collar {
+        Some(collar) => target(command, collar),
+        None => Ok(command),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Test parsing commands with and without arguments
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse("id 1234"), Ok(Command::Id(1234)));
+        assert_eq!(parse("c 2"), Ok(Command::Channel(Channel::Two)));
+        assert_eq!(parse("shock"), Ok(Command::Shock(None)));
+        assert_eq!(parse("v 30"), Ok(Command::Vibrate(Some(30))));
+        assert_eq!(parse("  v   30 "), Ok(Command::Vibrate(Some(30))));
+        assert_eq!(parse("t"), Ok(Command::Transmit(Repeat::Count(4))));
+        assert_eq!(
+            parse("transmit --for 1500ms"),
+            Ok(Command::Transmit(Repeat::For(Duration::from_millis(1500))))
+        );
+        assert_eq!(
+            parse("t --for 2.5s"),
+            Ok(Command::Transmit(Repeat::For(Duration::from_millis(2500))))
+        );
+        assert_eq!(
+            parse("protocol petrainer"),
+            Ok(Command::Protocol(Some(ProtocolKind::Petrainer)))
+        );
+        assert_eq!(parse("sniff off"), Ok(Command::Sniff(Some(false))));
+        assert_eq!(parse("learn"), Ok(Command::Learn(30)));
+        assert_eq!(parse("stop"), Ok(Command::Stop));
+        assert_eq!(parse("status"), Ok(Command::Status { json: false }));
+        assert_eq!(parse("status --json"), Ok(Command::Status { json: true }));
+        assert_eq!(parse("mode json"), Ok(Command::Mode(Some(Mode::Json))));
+        assert_eq!(parse("mode"), Ok(Command::Mode(None)));
+        assert_eq!(parse("stoppin off"), Ok(Command::StopPin(None)));
+        assert_eq!(parse("stoppin 9"), Ok(Command::StopPin(Some(9))));
+        assert_eq!(parse("ble"), Ok(Command::Bluetooth(None)));
+        assert_eq!(parse("ble off"), Ok(Command::Bluetooth(Some(None))));
+        assert_eq!(
+            parse("ble on 012345"),
+            Ok(Command::Bluetooth(Some(Some(12345))))
+        );
+    }
+
+    /// Test that out of range arguments are rejected
+    #[test]
+    fn rejects_out_of_range_arguments() {
+        assert_eq!(
+            parse("intensity 100"),
+            Err(ParseError::OutOfRange {
+                name: "Intensity",
+                min: 0,
+                max: 99
+            })
+        );
+        assert_eq!(
+            parse("id 99999999999").unwrap_err().to_string(),
+            "ID must be between 0 and 65535"
+        );
+        assert!(parse("rxpin 0").is_err());
+        assert!(parse("ble on 1000000").is_err());
+        assert_eq!(parse("stoppin 20"), Err(ParseError::ReservedPin(20)));
+        assert_eq!(parse("rxpin 11"), Ok(Command::RxPin(11)));
+        for pin in [12, 17, 18, 19, 20, 21] {
+            assert_eq!(
+                parse(&alloc::format!("rxpin {}", pin)),
+                Err(ParseError::ReservedPin(pin))
+            );
+        }
+        assert!(parse("transmit 65536").is_err());
+        assert!(parse("transmit --for 0ms").is_err());
+        assert!(parse("transmit --for 601s").is_err());
+    }
+
+    /// Test that missing, invalid and surplus arguments are told apart
+    #[test]
+    fn rejects_bad_arguments() {
+        assert_eq!(parse("id"), Err(ParseError::MissingArgument { name: "ID" }));
+        assert_eq!(
+            parse("shock abc"),
+            Err(ParseError::InvalidNumber {
+                name: "Intensity",
+                got: "abc".to_string()
+            })
+        );
+        assert_eq!(
+            parse("shock 30 40"),
+            Err(ParseError::UnexpectedArgument("40".to_string()))
+        );
+        assert_eq!(
+            parse("stop now"),
+            Err(ParseError::UnexpectedArgument("now".to_string()))
+        );
+        assert_eq!(
+            parse("transmit --for"),
+            Err(ParseError::MissingArgument { name: "Duration" })
+        );
+        assert_eq!(
+            parse("ble on"),
+            Err(ParseError::MissingArgument { name: "Passkey" })
+        );
+        assert_eq!(
+            parse("stoppin abc").unwrap_err().to_string(),
+            "Stop button GPIO must be a number, got abc"
+        );
+    }
+
+    /// Test that unknown commands and arguments are rejected
+    #[test]
+    fn rejects_unknown_input() {
+        assert_eq!(
+            parse("meow 1"),
+            Err(ParseError::UnknownCommand("meow".to_string()))
+        );
+        assert_eq!(parse(" "), Err(ParseError::Empty));
+        assert!(parse("protocol dogtra").is_err());
+        assert!(parse("sniff maybe").is_err());
+        assert!(parse("status --yaml").is_err());
+        assert_eq!(parse("mode yaml").unwrap_err().code(), "invalid_argument");
+        assert!(parse("transmit --for 1500").is_err());
+    }
+
+    /// Test splitting lines with quotes and escapes
+    #[test]
+    fn tokenizes_lines() {
+        assert_eq!(
+            tokenize(r#"one "two three" 'fo"ur' fi\ ve "" six"#),
+            Ok(vec![
+                "one".to_string(),
+                "two three".to_string(),
+                "fo\"ur".to_string(),
+                "fi ve".to_string(),
+                String::new(),
+                "six".to_string(),
+            ])
+        );
+        assert_eq!(tokenize(" \t "), Ok(vec![]));
+        assert_eq!(tokenize("a\"b c\"d"), Ok(vec!["ab cd".to_string()]));
+        assert_eq!(tokenize("\"open"), Err(ParseError::UnterminatedQuote));
+    }
+
+    /// Test that seconds are converted to milliseconds exactly
+    #[test]
+    fn parses_durations() {
+        let millis = |millis| Some(Duration::from_millis(millis));
+        assert_eq!(parse_duration("1500ms"), millis(1500));
+        assert_eq!(parse_duration("2s"), millis(2000));
+        assert_eq!(parse_duration("0.251s"), millis(251));
+        assert_eq!(parse_duration("1.004s"), millis(1004));
+        assert_eq!(parse_duration("599.999s"), millis(599_999));
+        assert_eq!(parse_duration("1.5s"), millis(1500));
+        assert_eq!(parse_duration(".25s"), millis(250));
+        assert_eq!(parse_duration("0s"), millis(0));
+        for invalid in [
+            "1.0005s", "s", ".s", "-1s", "+1s", "1e3s", "infs", "1..5s", "2", "+5ms", "-5ms", "ms",
+            "1.5ms",
+        ] {
+            assert_eq!(parse_duration(invalid), None, "{}", invalid);
+        }
+        // every millisecond up to the longest transmit
+        for expected in 0..=600_000 {
+            let text = format!("{}.{:03}s", expected / 1000, expected % 1000);
+            assert_eq!(parse_duration(&text), millis(expected));
+        }
+    }
+
+    /// Test one-off transmissions
+    #[test]
+    fn parses_send() {
+        assert_eq!(
+            parse("send id=1234 ch=1 shock 30 x4"),
+            Ok(Command::Send(
+                Overrides {
+                    id: Some(1234),
+                    channel: Some(Channel::One),
+                    action: Some(Action::Shock),
+                    intensity: Some(30),
+                    ..Overrides::default()
+                },
+                Repeat::Count(4)
+            ))
+        );
+        assert_eq!(
+            parse("send beep protocol=petrainer for=2s"),
+            Ok(Command::Send(
+                Overrides {
+                    action: Some(Action::Beep),
+                    protocol: Some(ProtocolKind::Petrainer),
+                    ..Overrides::default()
+                },
+                Repeat::For(Duration::from_secs(2))
+            ))
+        );
+        assert_eq!(
+            parse("send"),
+            Ok(Command::Send(Overrides::default(), Repeat::Count(4)))
+        );
+        assert_eq!(
+            parse("send vibrate x2"),
+            Ok(Command::Send(
+                Overrides {
+                    action: Some(Action::Vibrate),
+                    ..Overrides::default()
+                },
+                Repeat::Count(2)
+            ))
+        );
+
+        assert!(parse("send ch=3").is_err());
+        assert!(parse("send x0").is_err());
+        assert!(parse("send id=abc").is_err());
+        assert_eq!(
+            parse("send shock abc"),
+            Err(ParseError::UnexpectedArgument("abc".to_string()))
+        );
+        assert_eq!(
+            parse("send color=red"),
+            Err(ParseError::UnexpectedArgument("color=red".to_string()))
+        );
+    }
+
+    /// Test that overrides only replace the given fields
+    #[test]
+    fn applies_overrides() {
+        let packet = Packet {
+            id: 1,
+            channel: Channel::Zero,
+            action: Action::Beep,
+            intensity: 5,
+        };
+        let overrides = Overrides {
+            id: Some(1234),
+            intensity: Some(30),
+            ..Overrides::default()
+        };
+        assert_eq!(
+            overrides.apply(&packet),
+            Packet {
+                id: 1234,
+                intensity: 30,
+                ..packet
+            }
+        );
+    }
+
+    /// Test parsing the address book commands
+    #[test]
+    fn parses_collars() {
+        assert_eq!(parse("collar"), Ok(Command::Collar(CollarCommand::List)));
+        assert_eq!(
+            parse("collar add alice id=1234 max=40"),
+            Ok(Command::Collar(CollarCommand::Add {
+                name: "alice".to_string(),
+                id: Some(1234),
+                channel: None,
+                protocol: None,
+                max_intensity: Some(40),
+            }))
+        );
+        assert_eq!(
+            parse("collar use bob"),
+            Ok(Command::Collar(CollarCommand::Use("bob".to_string())))
+        );
+        assert_eq!(
+            parse("collar remove"),
+            Err(ParseError::MissingArgument { name: "Name" })
+        );
+        assert_eq!(
+            parse("collar add alice max=100"),
+            Err(ParseError::OutOfRange {
+                name: "Maximum intensity",
+                min: 0,
+                max: 99
+            })
+        );
+        assert!(matches!(
+            parse("collar add 'a b'"),
+            Err(ParseError::InvalidArgument { .. })
+        ));
+    }
+
+    /// Test parsing ramps
+    #[test]
+    fn parses_ramps() {
+        let ramp = |from, to, millis, curve| {
+            Ok(Command::Ramp {
+                ramp: Ramp {
+                    action: Action::Vibrate,
+                    from,
+                    to,
+                    duration: Duration::from_millis(millis),
+                    curve,
+                },
+                collar: None,
+            })
+        };
+        assert_eq!(
+            parse("ramp vibrate 10 60 5s"),
+            ramp(10, 60, 5000, Curve::Linear)
+        );
+        assert_eq!(
+            parse("ramp v 60 0 1500ms exp"),
+            ramp(60, 0, 1500, Curve::Exponential)
+        );
+        assert_eq!(
+            parse("ramp vibrate 0 40 4s stepped"),
+            ramp(0, 40, 4000, Curve::Stepped(4))
+        );
+        assert_eq!(
+            parse("ramp vibrate 0 40 4s stepped 8"),
+            ramp(0, 40, 4000, Curve::Stepped(8))
+        );
+        assert!(matches!(
+            parse("ramp shock 0 40 4s @alice"),
+            Ok(Command::Ramp {
+                collar: Some(_),
+                ..
+            })
+        ));
+
+        assert_eq!(
+            parse("ramp beep 0 40 4s"),
+            Err(ParseError::InvalidArgument {
+                expected: "shock or vibrate",
+                got: "beep".to_string()
+            })
+        );
+        assert_eq!(
+            parse("ramp vibrate 0 40"),
+            Err(ParseError::MissingArgument { name: "Duration" })
+        );
+        assert_eq!(
+            parse("ramp vibrate 0 100 4s"),
+            Err(ParseError::OutOfRange {
+                name: "End intensity",
+                min: 0,
+                max: 99
+            })
+        );
+        assert_eq!(
+            parse("ramp vibrate 0 40 4s stepped 1"),
+            Err(ParseError::OutOfRange {
+                name: "Amount of steps",
+                min: 2,
+                max: 20
+            })
+        );
+    }
+
+    /// Test parsing the bounds of random sessions
+    #[test]
+    fn parses_random() {
+        assert_eq!(
+            parse("random start"),
+            Ok(Command::Random(RandomCommand::Start {
+                settings: RandomSettings::default(),
+                collar: None,
+            }))
+        );
+        assert_eq!(
+            parse(concat!(
+                "random start actions=s,beep,shock intensity=5-15 gap=2s-90s for=1s ",
+                "session=60s seed=4000000000 @alice"
+            )),
+            Ok(Command::Random(RandomCommand::Start {
+                settings: RandomSettings {
+                    actions: vec![Action::Shock, Action::Beep],
+                    min_intensity: 5,
+                    max_intensity: 15,
+                    min_gap: Duration::from_secs(2),
+                    max_gap: Duration::from_secs(90),
+                    burst: Duration::from_secs(1),
+                    session: Duration::from_secs(60),
+                    seed: Some(4_000_000_000),
+                },
+                collar: Some("alice".to_string()),
+            }))
+        );
+        assert_eq!(
+            parse("random start intensity=20"),
+            Ok(Command::Random(RandomCommand::Start {
+                settings: RandomSettings {
+                    min_intensity: 20,
+                    max_intensity: 20,
+                    ..RandomSettings::default()
+                },
+                collar: None,
+            }))
+        );
+        assert_eq!(parse("random"), Ok(Command::Random(RandomCommand::Show)));
+        assert_eq!(
+            parse("random stop"),
+            Ok(Command::Random(RandomCommand::Stop))
+        );
+
+        assert_eq!(
+            parse("random start intensity=30-10"),
+            Err(ParseError::OutOfRange {
+                name: "Intensity",
+                min: 30,
+                max: 99
+            })
+        );
+        assert_eq!(
+            parse("random start gap=500ms-2s"),
+            Err(ParseError::OutOfRange {
+                name: "Gap in ms",
+                min: 1000,
+                max: 3_600_000
+            })
+        );
+        assert_eq!(
+            parse("random start actions=light"),
+            Err(ParseError::InvalidArgument {
+                expected: "shock, vibrate or beep",
+                got: "light".to_string()
+            })
+        );
+        assert!(matches!(
+            parse("random start seed=-1"),
+            Err(ParseError::InvalidNumber { name: "Seed", .. })
+        ));
+    }
+
+    /// Test defining and running sequences
+    #[test]
+    fn parses_macros() {
+        let step = |action, intensity, duration, delay| Step {
+            action,
+            intensity,
+            duration: Duration::from_millis(duration),
+            delay: Duration::from_millis(delay),
+        };
+        assert_eq!(
+            parse("macro define tease vibrate 30 pause 1s v 60 300ms pause 200ms beep"),
+            Ok(Command::Macro(MacroCommand::Define {
+                name: "tease".to_string(),
+                steps: vec![
+                    step(Action::Vibrate, 30, 500, 1000),
+                    step(Action::Vibrate, 60, 300, 200),
+                    step(Action::Beep, 0, 500, 0),
+                ],
+            }))
+        );
+        assert_eq!(
+            parse("macro run tease @alice"),
+            Ok(Command::Macro(MacroCommand::Run {
+                name: "tease".to_string(),
+                collar: Some("alice".to_string()),
+            }))
+        );
+        assert_eq!(parse("macro"), Ok(Command::Macro(MacroCommand::List)));
+        assert_eq!(
+            parse("macro delete tease"),
+            Ok(Command::Macro(MacroCommand::Delete("tease".to_string())))
+        );
+
+        assert_eq!(
+            parse("macro define tease"),
+            Err(ParseError::MissingArgument { name: "Steps" })
+        );
+        assert_eq!(
+            parse("macro define tease pause 1s beep"),
+            Err(ParseError::UnexpectedArgument("pause".to_string()))
+        );
+        assert_eq!(
+            parse("macro define tease shock 500ms"),
+            Err(ParseError::InvalidNumber {
+                name: "Intensity",
+                got: "500ms".to_string()
+            })
+        );
+        assert_eq!(
+            parse("macro define tease beep pause 2m"),
+            Err(ParseError::InvalidArgument {
+                expected: "a duration like 1500ms or 2s",
+                got: "2m".to_string()
+            })
+        );
+        assert_eq!(
+            parse(&alloc::format!("macro define long{}", " beep".repeat(33))),
+            Err(ParseError::OutOfRange {
+                name: "Amount of steps",
+                min: 1,
+                max: 32
+            })
+        );
+    }
+
+    /// Test setting the warning of a collar
+    #[test]
+    fn parses_warnings() {
+        assert_eq!(
+            parse("collar warn alice vibrate 20 delay=2s"),
+            Ok(Command::Collar(CollarCommand::Warn {
+                name: "alice".to_string(),
+                warning: Some(Warning {
+                    action: Action::Vibrate,
+                    intensity: 20,
+                    duration: DEFAULT_WARNING_DURATION,
+                    delay: Duration::from_secs(2),
+                }),
+            }))
+        );
+        assert_eq!(
+            parse("collar warn alice beep for=300ms delay=0ms"),
+            Ok(Command::Collar(CollarCommand::Warn {
+                name: "alice".to_string(),
+                warning: Some(Warning {
+                    action: Action::Beep,
+                    intensity: 0,
+                    duration: Duration::from_millis(300),
+                    delay: Duration::ZERO,
+                }),
+            }))
+        );
+        assert_eq!(
+            parse("collar warn alice off"),
+            Ok(Command::Collar(CollarCommand::Warn {
+                name: "alice".to_string(),
+                warning: None,
+            }))
+        );
+        assert_eq!(
+            parse("collar warn alice vibrate"),
+            Err(ParseError::MissingArgument { name: "Intensity" })
+        );
+        assert_eq!(
+            parse("collar warn alice beep for=0ms"),
+            Err(ParseError::OutOfRange {
+                name: "Warning duration in ms",
+                min: 1,
+                max: 10000
+            })
+        );
+        assert!(matches!(
+            parse("collar warn alice shock 20"),
+            Err(ParseError::InvalidArgument { .. })
+        ));
+    }
+
+    /// Test sending to a collar by name
+    #[test]
+    fn parses_targets() {
+        let to_alice = |action, intensity| Overrides {
+            collar: Some("alice".to_string()),
+            action,
+            intensity,
+            ..Overrides::default()
+        };
+        assert_eq!(
+            parse("shock 20 @alice"),
+            Ok(Command::Send(
+                to_alice(Some(Action::Shock), Some(20)),
+                Repeat::Count(4)
+            ))
+        );
+        assert_eq!(
+            parse("beep @alice"),
+            Ok(Command::Send(
+                to_alice(Some(Action::Beep), None),
+                Repeat::Count(4)
+            ))
+        );
+        assert_eq!(
+            parse("transmit @alice 2"),
+            Ok(Command::Send(to_alice(None, None), Repeat::Count(2)))
+        );
+        assert_eq!(
+            parse("send @alice vibrate 5 x1"),
+            Ok(Command::Send(
+                to_alice(Some(Action::Vibrate), Some(5)),
+                Repeat::Count(1)
+            ))
+        );
+        assert_eq!(
+            parse("id 5 @alice"),
+            Err(ParseError::UnexpectedArgument("@alice".to_string()))
+        );
+        assert_eq!(
+            parse("shock @alice @bob"),
+            Err(ParseError::UnexpectedArgument("@bob".to_string()))
+        );
+    }
+
+    /// Test parsing limit changes
+    #[test]
+    fn parses_limits() {
+        assert_eq!(
+            parse("limits"),
+            Ok(Command::Limits {
+                collar: None,
+                changes: LimitChanges::default()
+            })
+        );
+        assert_eq!(
+            parse("limits @alice shock=20 duration=2s cooldown=0s"),
+            Ok(Command::Limits {
+                collar: Some("alice".to_string()),
+                changes: LimitChanges {
+                    max_shock: Some(20),
+                    max_duration: Some(Duration::from_secs(2)),
+                    cooldown: Some(Duration::ZERO),
+                    ..LimitChanges::default()
+                }
+            })
+        );
+        assert_eq!(
+            parse("limits vibrate=100"),
+            Err(ParseError::OutOfRange {
+                name: "Vibrate limit",
+                min: 0,
+                max: 99
+            })
+        );
+        assert_eq!(
+            parse("limits cooldown=2h"),
+            Err(ParseError::InvalidArgument {
+                expected: "a duration like 1500ms or 2s",
+                got: "2h".to_string()
+            })
+        );
+        assert_eq!(parse("unlock"), Ok(Command::Unlock));
+    }
+}
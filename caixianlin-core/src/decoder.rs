@@ -0,0 +1,226 @@
+use alloc::vec::Vec;
+
+use crate::{
+    frame::Frame,
+    packet::{DecodeError, Packet},
+    protocol::{Protocol, Symbol},
+};
+
+/// Default tolerance for matching pulses against the protocol timings, in percent
+pub const DEFAULT_TOLERANCE: u8 = 25;
+
+/// Something noteworthy that happened while decoding a capture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A sync symbol was found, starting a new frame
+    Sync { index: usize },
+    /// A pulse matched none of the symbols, discarding the current frame
+    BitError { index: usize, high: u32, low: u32 },
+    /// A frame was complete but did not pass validation
+    InvalidFrame {
+        index: usize,
+        frame: Frame,
+        error: DecodeError,
+    },
+    /// A frame was decoded successfully
+    Packet { index: usize, packet: Packet },
+}
+
+/// Decoder for captured pulse timings
+///
+/// Captures are given as pairs of high and low durations in microseconds, one pair per symbol.
+/// A low duration of zero stands for a final pulse whose end was not captured.
+pub struct Decoder<'a> {
+    /// Protocol to decode
+    protocol: &'a dyn Protocol,
+    /// Tolerance for matching pulses, in percent
+    tolerance: u8,
+}
+
+impl<'a> Decoder<'a> {
+    /// Create a new decoder with the default tolerance
+    pub fn new(protocol: &'a dyn Protocol) -> Self {
+        Self {
+            protocol,
+            tolerance: DEFAULT_TOLERANCE,
+        }
+    }
+
+    /// Set the tolerance for matching pulses, in percent
+    pub fn with_tolerance(self, tolerance: u8) -> Self {
+        Self { tolerance, ..self }
+    }
+
+    /// Decode a capture into a list of events
+    ///
+    /// Every sync symbol starts a new frame, which is validated as soon as enough bits were
+    /// collected.
+    pub fn decode(&self, durations: &[(u32, u32)]) -> Vec<Event> {
+        let timings = self.protocol.timings();
+        let mut events = Vec::new();
+        let mut frame: Option<Frame> = None;
+
+        for (index, &(high, low)) in durations.iter().enumerate() {
+            let clamp = |duration: u32| u16::try_from(duration).unwrap_or(u16::MAX);
+            match (
+                timings.classify(clamp(high), clamp(low), self.tolerance),
+                frame.as_mut(),
+            ) {
+                (Some(Symbol::Sync), _) => {
+                    events.push(Event::Sync { index });
+                    frame = Some(Frame::new());
+                }
+                (Some(Symbol::One), Some(bits)) => bits.push(true),
+                (Some(Symbol::Zero), Some(bits)) => bits.push(false),
+                (None, Some(_)) => {
+                    events.push(Event::BitError { index, high, low });
+                    frame = None;
+                }
+                (_, None) => {}
+            }
+
+            if let Some(bits) = frame {
+                if bits.len() == self.protocol.frame_len() {
+                    events.push(match self.protocol.decode(&bits) {
+                        Ok(packet) => Event::Packet { index, packet },
+                        Err(error) => Event::InvalidFrame {
+                            index,
+                            frame: bits,
+                            error,
+                        },
+                    });
+                    frame = None;
+                }
+            }
+        }
+
+        events
+    }
+
+    /// Decode a capture and keep only the valid packets
+    pub fn packets(&self, durations: &[(u32, u32)]) -> Vec<Packet> {
+        self.decode(durations)
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Packet { packet, .. } => Some(packet),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        packet::{Action, Channel},
+        protocol::{CaiXianLin, ProtocolKind},
+    };
+
+    /// Build the durations the transmitter would emit for a packet
+    fn capture(protocol: &dyn Protocol, packet: &Packet) -> Vec<(u32, u32)> {
+        let frame = protocol.encode(packet).unwrap();
+        protocol
+            .timings()
+            .symbols(&frame)
+            .map(|timing| (timing.high as u32, timing.low as u32))
+            .collect()
+    }
+
+    fn packet() -> Packet {
+        Packet {
+            id: 0b0010110010111110,
+            channel: Channel::Zero,
+            action: Action::Vibrate,
+            intensity: 0b00110010,
+        }
+    }
+
+    /// Test that what the encoder emits decodes to the same packet
+    #[test]
+    fn decodes_encoded_signal() {
+        for kind in ProtocolKind::ALL {
+            let protocol = kind.protocol();
+            let durations = capture(protocol, &packet());
+            assert_eq!(Decoder::new(protocol).packets(&durations), vec![packet()]);
+        }
+    }
+
+    /// Test decoding repeated frames with jitter and a truncated last pulse
+    #[test]
+    fn decodes_jittery_repeated_frames() {
+        let mut durations = capture(&CaiXianLin, &packet());
+        durations.extend(capture(&CaiXianLin, &packet()));
+        for (index, (high, low)) in durations.iter_mut().enumerate() {
+            if index % 2 == 0 {
+                *high += *high / 10;
+            } else {
+                *low -= *low / 10;
+            }
+        }
+        durations.last_mut().unwrap().1 = 0;
+
+        let events = Decoder::new(&CaiXianLin).decode(&durations);
+        assert_eq!(
+            events,
+            vec![
+                Event::Sync { index: 0 },
+                Event::Packet {
+                    index: 43,
+                    packet: packet()
+                },
+                Event::Sync { index: 44 },
+                Event::Packet {
+                    index: 87,
+                    packet: packet()
+                },
+            ]
+        );
+    }
+
+    /// Test that pulses outside the tolerance are reported as bit errors
+    #[test]
+    fn reports_bit_errors() {
+        let mut durations = capture(&CaiXianLin, &packet());
+        durations[5] = (550, 550);
+
+        let decoder = Decoder::new(&CaiXianLin);
+        assert_eq!(
+            decoder.decode(&durations)[1],
+            Event::BitError {
+                index: 5,
+                high: 550,
+                low: 550
+            }
+        );
+        assert!(decoder.packets(&durations).is_empty());
+
+        let strict = Decoder::new(&CaiXianLin).with_tolerance(5);
+        durations = capture(&CaiXianLin, &packet());
+        durations[5].0 += durations[5].0 / 5;
+        assert!(strict.packets(&durations).is_empty());
+    }
+
+    /// Test that checksum failures are reported with the offending frame
+    #[test]
+    fn reports_checksum_failures() {
+        let mut durations = capture(&CaiXianLin, &packet());
+        // flip the last data bit
+        let timings = CaiXianLin.timings();
+        durations[32] = if durations[32] == (timings.one.high as u32, timings.one.low as u32) {
+            (timings.zero.high as u32, timings.zero.low as u32)
+        } else {
+            (timings.one.high as u32, timings.one.low as u32)
+        };
+
+        let events = Decoder::new(&CaiXianLin).decode(&durations);
+        assert!(matches!(
+            events[1],
+            Event::InvalidFrame {
+                index: 43,
+                error: DecodeError::ChecksumMismatch { .. },
+                ..
+            }
+        ));
+    }
+}
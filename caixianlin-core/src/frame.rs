@@ -0,0 +1,224 @@
+use alloc::vec::Vec;
+
+/// Error returned when a frame would exceed its capacity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("frames can hold at most {capacity} bits, got {0}", capacity = Frame::CAPACITY)]
+pub struct FrameTooLong(pub usize);
+
+/// Fixed-size sequence of bits that is transmitted in one go
+///
+/// The bits are stored in a u64, with the first bit of the frame being the most significant one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Frame {
+    /// Bits of the frame, right-aligned
+    bits: u64,
+    /// Amount of bits in the frame
+    len: u8,
+}
+
+impl Frame {
+    /// Maximum amount of bits a frame can hold
+    pub const CAPACITY: usize = 64;
+
+    /// Create an empty frame
+    pub const fn new() -> Self {
+        Self { bits: 0, len: 0 }
+    }
+
+    /// Amount of bits in the frame
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Whether the frame contains no bits
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Append a bit to the end of the frame
+    ///
+    /// # Panics
+    ///
+    /// Panics if the frame is already full.
+    pub fn push(&mut self, bit: bool) {
+        assert!(self.len() < Self::CAPACITY, "frame is full");
+        self.bits = (self.bits << 1) | bit as u64;
+        self.len += 1;
+    }
+
+    /// Append the lowest `count` bits of `value`, most significant bit first
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bits do not fit into the frame.
+    pub fn push_bits(&mut self, value: u64, count: usize) {
+        for i in (0..count).rev() {
+            self.push((value >> i) & 1 == 1);
+        }
+    }
+
+    /// Get the bit at the given index
+    pub fn get(&self, index: usize) -> Option<bool> {
+        if index >= self.len() {
+            return None;
+        }
+        Some((self.bits >> (self.len() - 1 - index)) & 1 == 1)
+    }
+
+    /// Read `count` bits starting at `start` as an integer, most significant bit first
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn field(&self, start: usize, count: usize) -> u64 {
+        assert!(start + count <= self.len(), "field out of bounds");
+        if count == 0 {
+            return 0;
+        }
+        let shifted = self.bits >> (self.len() - start - count);
+        if count == 64 {
+            shifted
+        } else {
+            shifted & ((1 << count) - 1)
+        }
+    }
+
+    /// Copy `count` bits starting at `start` into a new frame
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn slice(&self, start: usize, count: usize) -> Frame {
+        let mut frame = Frame::new();
+        frame.push_bits(self.field(start, count), count);
+        frame
+    }
+
+    /// Iterate over the bits of the frame
+    pub fn iter(&self) -> Bits {
+        Bits {
+            frame: *self,
+            index: 0,
+        }
+    }
+}
+
+/// Iterator over the bits of a frame
+#[derive(Debug, Clone)]
+pub struct Bits {
+    frame: Frame,
+    index: usize,
+}
+
+impl Iterator for Bits {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<bool> {
+        let bit = self.frame.get(self.index)?;
+        self.index += 1;
+        Some(bit)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.frame.len() - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Bits {}
+
+impl IntoIterator for Frame {
+    type Item = bool;
+    type IntoIter = Bits;
+
+    fn into_iter(self) -> Bits {
+        self.iter()
+    }
+}
+
+impl IntoIterator for &Frame {
+    type Item = bool;
+    type IntoIter = Bits;
+
+    fn into_iter(self) -> Bits {
+        self.iter()
+    }
+}
+
+impl Extend<bool> for Frame {
+    /// Append bits to the frame
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bits do not fit into the frame.
+    fn extend<T: IntoIterator<Item = bool>>(&mut self, iter: T) {
+        for bit in iter {
+            self.push(bit);
+        }
+    }
+}
+
+impl TryFrom<&[bool]> for Frame {
+    type Error = FrameTooLong;
+
+    /// Convert a slice of bits to a Frame
+    fn try_from(bits: &[bool]) -> Result<Self, Self::Error> {
+        if bits.len() > Self::CAPACITY {
+            return Err(FrameTooLong(bits.len()));
+        }
+        let mut frame = Frame::new();
+        frame.extend(bits.iter().copied());
+        Ok(frame)
+    }
+}
+
+impl From<Frame> for Vec<bool> {
+    /// Convert a Frame to a vector of bits
+    fn from(frame: Frame) -> Self {
+        frame.iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that bits come out in the order they were pushed
+    #[test]
+    fn preserves_bit_order() {
+        let bits = [true, false, false, true, true, false, true];
+        let frame = Frame::try_from(&bits[..]).unwrap();
+        assert_eq!(frame.len(), bits.len());
+        assert_eq!(Vec::from(frame), bits.to_vec());
+        assert_eq!(frame.get(3), Some(true));
+        assert_eq!(frame.get(7), None);
+    }
+
+    /// Test reading integer fields out of a frame
+    #[test]
+    fn reads_fields() {
+        let mut frame = Frame::new();
+        frame.push_bits(0b1011, 4);
+        frame.push_bits(0xABCD, 16);
+        assert_eq!(frame.field(0, 4), 0b1011);
+        assert_eq!(frame.field(4, 16), 0xABCD);
+        assert_eq!(frame.field(2, 6), 0b111010);
+    }
+
+    /// Test that a full frame can be filled and read
+    #[test]
+    fn holds_full_capacity() {
+        let mut frame = Frame::new();
+        frame.push_bits(u64::MAX, 64);
+        assert_eq!(frame.len(), Frame::CAPACITY);
+        assert_eq!(frame.field(0, 64), u64::MAX);
+        assert_eq!(frame.iter().filter(|bit| *bit).count(), 64);
+    }
+
+    /// Test that oversized slices are rejected
+    #[test]
+    fn rejects_too_many_bits() {
+        let bits = [false; 65];
+        assert_eq!(Frame::try_from(&bits[..]), Err(FrameTooLong(65)));
+    }
+}
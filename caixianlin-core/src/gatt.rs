@@ -0,0 +1,211 @@
+//! Values of the BLE GATT control service.
+//!
+//! Every setting of the shell has its own characteristic, so simple BLE apps can read and write
+//! them without a framing layer. Writes are turned into the same [`Command`]s the serial shell
+//! executes, so they store the configuration and respect the limits in the same way. Numbers are
+//! little endian and actions, channels and protocols use the same bytes as the binary protocol.
+
+use alloc::vec::Vec;
+
+use crate::{
+    binary::{read_repeat, ErrorCode, StatusReport},
+    command::{Command, DEFAULT_REPEAT},
+    packet::{Action, Channel},
+    status::Status,
+};
+
+/// Characteristics of the control service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Characteristic {
+    /// Shocker ID as u16, readable and writable
+    Id,
+    /// Channel as u8, readable and writable
+    Channel,
+    /// Action as u8, readable and writable
+    Action,
+    /// Intensity as u8, readable and writable
+    Intensity,
+    /// Writing transmits the configured packet
+    ///
+    /// An empty value sends the default amount of frames, otherwise the value is the repeat kind
+    /// and u32 value of a binary transmit request.
+    Transmit,
+    /// Writing anything drops all queued transmissions
+    Stop,
+    /// Status report of the binary protocol, readable and notified on changes
+    Status,
+}
+
+impl Characteristic {
+    /// All characteristics in the order they are registered
+    pub const ALL: [Characteristic; 7] = [
+        Characteristic::Id,
+        Characteristic::Channel,
+        Characteristic::Action,
+        Characteristic::Intensity,
+        Characteristic::Transmit,
+        Characteristic::Stop,
+        Characteristic::Status,
+    ];
+
+    /// Whether the characteristic has a value that can be read
+    pub fn is_readable(&self) -> bool {
+        !matches!(self, Characteristic::Transmit | Characteristic::Stop)
+    }
+
+    /// Whether the characteristic can be written
+    pub fn is_writable(&self) -> bool {
+        !matches!(self, Characteristic::Status)
+    }
+
+    /// Whether changes of the value are notified
+    pub fn notifies(&self) -> bool {
+        matches!(self, Characteristic::Status)
+    }
+
+    /// Value of the characteristic for the given status, empty if it is not readable
+    pub fn read(&self, status: &Status) -> Vec<u8> {
+        match self {
+            Characteristic::Id => status.id.to_le_bytes().to_vec(),
+            Characteristic::Channel => alloc::vec![status.channel as u8],
+            Characteristic::Action => alloc::vec![status.action as u8],
+            Characteristic::Intensity => alloc::vec![status.intensity],
+            Characteristic::Transmit | Characteristic::Stop => Vec::new(),
+            Characteristic::Status => {
+                let mut value = Vec::new();
+                StatusReport::from(status).encode(&mut value);
+                value
+            }
+        }
+    }
+
+    /// Turn a written value into the command that the shell executes
+    pub fn write(&self, value: &[u8]) -> Result<Command, ErrorCode> {
+        match (self, value) {
+            (Characteristic::Id, [low, high]) => Ok(Command::Id(u16::from_le_bytes([*low, *high]))),
+            (Characteristic::Channel, [channel]) => Ok(Command::Channel(
+                Channel::try_from(*channel).map_err(|_| ErrorCode::InvalidValue)?,
+            )),
+            (Characteristic::Action, [action]) => {
+                match Action::try_from(*action).map_err(|_| ErrorCode::InvalidValue)? {
+                    Action::Shock => Ok(Command::Shock(None)),
+                    Action::Vibrate => Ok(Command::Vibrate(None)),
+                    Action::Beep => Ok(Command::Beep),
+                    Action::Light => Ok(Command::Light),
+                }
+            }
+            (Characteristic::Intensity, [intensity]) if *intensity > 99 => {
+                Err(ErrorCode::InvalidValue)
+            }
+            (Characteristic::Intensity, [intensity]) => Ok(Command::Intensity(*intensity)),
+            (Characteristic::Transmit, []) => Ok(Command::Transmit(DEFAULT_REPEAT)),
+            (Characteristic::Transmit, [kind, a, b, c, d]) => Ok(Command::Transmit(read_repeat(
+                *kind,
+                u32::from_le_bytes([*a, *b, *c, *d]),
+            )?)),
+            (Characteristic::Stop, _) => Ok(Command::Stop),
+            (Characteristic::Status, _) => Err(ErrorCode::UnknownType),
+            _ => Err(ErrorCode::Length),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        queue::Repeat, shell::Shell, storage::MemoryStorage, transmitter::RecordingTransmitter,
+    };
+    use alloc::{string::String, vec};
+    use core::time::Duration;
+
+    /// Test decoding written values
+    #[test]
+    fn decodes_writes() {
+        assert_eq!(
+            Characteristic::Id.write(&[0xd2, 0x04]),
+            Ok(Command::Id(1234))
+        );
+        assert_eq!(Characteristic::Id.write(&[1]), Err(ErrorCode::Length));
+        assert_eq!(
+            Characteristic::Channel.write(&[2]),
+            Ok(Command::Channel(Channel::Two))
+        );
+        assert_eq!(
+            Characteristic::Channel.write(&[3]),
+            Err(ErrorCode::InvalidValue)
+        );
+        assert_eq!(
+            Characteristic::Action.write(&[Action::Vibrate as u8]),
+            Ok(Command::Vibrate(None))
+        );
+        assert_eq!(
+            Characteristic::Intensity.write(&[100]),
+            Err(ErrorCode::InvalidValue)
+        );
+        assert_eq!(
+            Characteristic::Transmit.write(&[]),
+            Ok(Command::Transmit(Repeat::Count(4)))
+        );
+        assert_eq!(
+            Characteristic::Transmit.write(&[1, 0xe8, 0x03, 0, 0]),
+            Ok(Command::Transmit(Repeat::For(Duration::from_secs(1))))
+        );
+        assert_eq!(Characteristic::Stop.write(&[1]), Ok(Command::Stop));
+        assert_eq!(
+            Characteristic::Status.write(&[]),
+            Err(ErrorCode::UnknownType)
+        );
+    }
+
+    /// Test that writes change the shell and reads follow it
+    #[test]
+    fn controls_shell() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        let mut output = String::new();
+        for (characteristic, value) in [
+            (Characteristic::Id, &[0x39, 0x05][..]),
+            (Characteristic::Action, &[Action::Vibrate as u8]),
+            (Characteristic::Intensity, &[25]),
+            (Characteristic::Transmit, &[]),
+        ] {
+            let command = characteristic.write(value).unwrap();
+            output += &shell.process_parsed(command, Duration::ZERO).unwrap();
+        }
+        assert_eq!(
+            output,
+            concat!(
+                "Setting ID to 1337\n",
+                "Setting action to vibrate 1\n",
+                "Setting intensity to 25\n",
+                "Sending Vibrate to shocker 1337 on channel Zero with intensity 25\n",
+                "Repeating 4 times\n"
+            )
+        );
+        assert_eq!(shell.queue().len(), 1);
+
+        let status = shell.status(Duration::ZERO);
+        assert_eq!(Characteristic::Id.read(&status), [0x39, 0x05]);
+        assert_eq!(Characteristic::Intensity.read(&status), [25]);
+        assert_eq!(Characteristic::Stop.read(&status), []);
+        // protocol, packet, one queued job, not transmitting, no uptime
+        let mut report = vec![0, 0x39, 0x05, 0, Action::Vibrate as u8, 25];
+        report.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(Characteristic::Status.read(&status), report);
+
+        // transmissions beyond the limits are rejected
+        let mut reply = String::new();
+        shell
+            .process_command("unlock", Duration::ZERO, &mut reply)
+            .unwrap();
+        shell
+            .process_command("limits vibrate=20", Duration::ZERO, &mut reply)
+            .unwrap();
+        let command = Characteristic::Transmit.write(&[]).unwrap();
+        assert_eq!(
+            shell.process_parsed(command, Duration::ZERO),
+            Err(ErrorCode::Limit)
+        );
+        assert_eq!(shell.queue().len(), 1);
+    }
+}
@@ -0,0 +1,148 @@
+use core::fmt::{self, Write};
+
+/// Write a string as a quoted and escaped JSON string
+pub fn write_string(output: &mut impl Write, value: &str) -> fmt::Result {
+    output.write_char('"')?;
+    for char in value.chars() {
+        match char {
+            '"' => output.write_str("\\\"")?,
+            '\\' => output.write_str("\\\\")?,
+            '\n' => output.write_str("\\n")?,
+            '\r' => output.write_str("\\r")?,
+            '\t' => output.write_str("\\t")?,
+            char if u32::from(char) < 0x20 => write!(output, "\\u{:04x}", u32::from(char))?,
+            char => output.write_char(char)?,
+        }
+    }
+    output.write_char('"')
+}
+
+/// Writer for a JSON object
+///
+/// The opening brace is written on creation and the closing one by [`Object::finish`].
+pub struct Object<'a, W: Write> {
+    /// Output the object is written to
+    output: &'a mut W,
+    /// Whether no field was written yet
+    empty: bool,
+}
+
+impl<'a, W: Write> Object<'a, W> {
+    /// Start a new object
+    pub fn new(output: &'a mut W) -> Result<Self, fmt::Error> {
+        output.write_char('{')?;
+        Ok(Self {
+            output,
+            empty: true,
+        })
+    }
+
+    /// Write the key of the next field
+    fn key(&mut self, key: &str) -> fmt::Result {
+        if !self.empty {
+            self.output.write_char(',')?;
+        }
+        self.empty = false;
+        write_string(self.output, key)?;
+        self.output.write_char(':')
+    }
+
+    /// Add a string field
+    pub fn string(&mut self, key: &str, value: &str) -> Result<&mut Self, fmt::Error> {
+        self.key(key)?;
+        write_string(self.output, value)?;
+        Ok(self)
+    }
+
+    /// Add a number field
+    pub fn number(&mut self, key: &str, value: impl Into<i64>) -> Result<&mut Self, fmt::Error> {
+        self.key(key)?;
+        write!(self.output, "{}", value.into())?;
+        Ok(self)
+    }
+
+    /// Add a boolean field
+    pub fn bool(&mut self, key: &str, value: bool) -> Result<&mut Self, fmt::Error> {
+        self.key(key)?;
+        write!(self.output, "{}", value)?;
+        Ok(self)
+    }
+
+    /// Add a null field
+    pub fn null(&mut self, key: &str) -> Result<&mut Self, fmt::Error> {
+        self.key(key)?;
+        self.output.write_str("null")?;
+        Ok(self)
+    }
+
+    /// Add a field that is null if the value is missing
+    pub fn optional<T>(
+        &mut self,
+        key: &str,
+        value: Option<T>,
+        write: impl for<'o> FnOnce(&'o mut Self, &str, T) -> Result<&'o mut Self, fmt::Error>,
+    ) -> Result<&mut Self, fmt::Error> {
+        match value {
+            Some(value) => write(self, key, value),
+            None => self.null(key),
+        }
+    }
+
+    /// Add a nested object
+    pub fn object(
+        &mut self,
+        key: &str,
+        write: impl FnOnce(&mut Object<W>) -> fmt::Result,
+    ) -> Result<&mut Self, fmt::Error> {
+        self.key(key)?;
+        let mut object = Object::new(&mut *self.output)?;
+        write(&mut object)?;
+        object.finish()?;
+        Ok(self)
+    }
+
+    /// Close the object
+    pub fn finish(self) -> fmt::Result {
+        self.output.write_char('}')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    /// Test writing objects with all kinds of fields
+    #[test]
+    fn writes_objects() {
+        let mut output = String::new();
+        let mut object = Object::new(&mut output).unwrap();
+        object
+            .string("name", "say \"hi\"\n")
+            .unwrap()
+            .number("id", 1234u16)
+            .unwrap()
+            .bool("ok", false)
+            .unwrap()
+            .optional("pin", None::<u8>, Object::number)
+            .unwrap()
+            .object("nested", |object| {
+                object.number("depth", -1)?;
+                Ok(())
+            })
+            .unwrap();
+        object.finish().unwrap();
+        assert_eq!(
+            output,
+            r#"{"name":"say \"hi\"\n","id":1234,"ok":false,"pin":null,"nested":{"depth":-1}}"#
+        );
+    }
+
+    /// Test that control characters are escaped
+    #[test]
+    fn escapes_control_characters() {
+        let mut output = String::new();
+        write_string(&mut output, "a\u{7}\\b").unwrap();
+        assert_eq!(output, r#""a\u0007\\b""#);
+    }
+}
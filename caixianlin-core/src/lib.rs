@@ -0,0 +1,27 @@
+//! Protocol logic for controlling 433 MHz OOK collars.
+//!
+//! This crate does not depend on any hardware, so it can be tested on the host.
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod binary;
+pub mod collar;
+pub mod command;
+pub mod decoder;
+pub mod frame;
+pub mod gatt;
+pub mod json;
+pub mod limits;
+pub mod line;
+pub mod packet;
+pub mod protocol;
+pub mod queue;
+pub mod ramp;
+pub mod random;
+pub mod sequence;
+pub mod shell;
+pub mod status;
+pub mod storage;
+pub mod transmitter;
+pub mod warning;
@@ -0,0 +1,256 @@
+use core::{
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use crate::{
+    command::MAX_TRANSMIT_DURATION,
+    packet::{Action, Channel, Packet},
+    protocol::ProtocolKind,
+};
+
+/// Longest cooldown that can be configured
+pub const MAX_COOLDOWN: Duration = Duration::from_secs(3600);
+
+/// Length of [`Limits::encode`]
+pub const ENCODED_LIMITS_LENGTH: usize = 10;
+
+/// Collar a transmission is addressed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    /// Protocol of the collar
+    pub protocol: ProtocolKind,
+    /// Shocker ID
+    pub id: u16,
+    /// Channel the collar listens on
+    pub channel: Channel,
+}
+
+impl Target {
+    /// Collar a packet is addressed to
+    pub fn new(protocol: ProtocolKind, packet: &Packet) -> Self {
+        Self {
+            protocol,
+            id: packet.id,
+            channel: packet.channel,
+        }
+    }
+}
+
+/// Safety limits every transmission has to respect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Highest shock intensity
+    pub max_shock: u8,
+    /// Highest vibrate intensity
+    pub max_vibrate: u8,
+    /// Longest time a single transmission may keep the collar going
+    pub max_duration: Duration,
+    /// Shortest time between the end of a shock and the start of the next one to the same
+    /// collar
+    pub cooldown: Duration,
+}
+
+impl Limits {
+    /// Limits that restrict nothing beyond what commands can express anyway
+    pub const NONE: Limits = Limits {
+        max_shock: 99,
+        max_vibrate: 99,
+        max_duration: MAX_TRANSMIT_DURATION,
+        cooldown: Duration::ZERO,
+    };
+
+    /// The stricter of two limits, field by field
+    pub fn strictest(&self, other: &Limits) -> Limits {
+        Limits {
+            max_shock: self.max_shock.min(other.max_shock),
+            max_vibrate: self.max_vibrate.min(other.max_vibrate),
+            max_duration: self.max_duration.min(other.max_duration),
+            cooldown: self.cooldown.max(other.cooldown),
+        }
+    }
+
+    /// Check the intensity of a packet
+    pub fn check_intensity(&self, packet: &Packet) -> Result<(), LimitError> {
+        let max = match packet.action {
+            Action::Shock => self.max_shock,
+            Action::Vibrate => self.max_vibrate,
+            Action::Beep | Action::Light => return Ok(()),
+        };
+        if packet.intensity > max {
+            return Err(LimitError::Intensity {
+                action: packet.action,
+                intensity: packet.intensity,
+                max,
+            });
+        }
+        Ok(())
+    }
+
+    /// Serialize the limits for storage
+    ///
+    /// The format is `max shock, max vibrate, max duration in ms (u32 little endian), cooldown in
+    /// ms (u32 little endian)`.
+    pub fn encode(&self) -> [u8; ENCODED_LIMITS_LENGTH] {
+        let mut bytes = [0; ENCODED_LIMITS_LENGTH];
+        bytes[0] = self.max_shock;
+        bytes[1] = self.max_vibrate;
+        bytes[2..6].copy_from_slice(&millis(self.max_duration).to_le_bytes());
+        bytes[6..10].copy_from_slice(&millis(self.cooldown).to_le_bytes());
+        bytes
+    }
+
+    /// Read limits written by [`Limits::encode`], `None` if they are out of range
+    pub fn decode(bytes: &[u8; ENCODED_LIMITS_LENGTH]) -> Option<Self> {
+        let [max_shock, max_vibrate, d0, d1, d2, d3, c0, c1, c2, c3] = *bytes;
+        let limits = Limits {
+            max_shock,
+            max_vibrate,
+            max_duration: Duration::from_millis(u32::from_le_bytes([d0, d1, d2, d3]) as u64),
+            cooldown: Duration::from_millis(u32::from_le_bytes([c0, c1, c2, c3]) as u64),
+        };
+        let valid = max_shock <= 99
+            && max_vibrate <= 99
+            && !limits.max_duration.is_zero()
+            && limits.max_duration <= MAX_TRANSMIT_DURATION
+            && limits.cooldown <= MAX_COOLDOWN;
+        valid.then_some(limits)
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+impl Display for Limits {
+    /// Describe the limits in a sentence
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "shock up to {}, vibrate up to {}, transmit up to {}ms, {}ms between shocks",
+            self.max_shock,
+            self.max_vibrate,
+            self.max_duration.as_millis(),
+            self.cooldown.as_millis()
+        )
+    }
+}
+
+/// Duration in whole milliseconds, saturating at the largest u32
+fn millis(duration: Duration) -> u32 {
+    u32::try_from(duration.as_millis()).unwrap_or(u32::MAX)
+}
+
+/// Reasons a transmission is rejected by the limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum LimitError {
+    /// The intensity is above the maximum of the action
+    #[error("{action:?} intensity {intensity} exceeds the limit of {max}")]
+    Intensity {
+        action: Action,
+        intensity: u8,
+        max: u8,
+    },
+    /// The transmission would last too long
+    #[error(
+        "Transmission of {}ms exceeds the limit of {}ms",
+        duration.as_millis(),
+        max.as_millis()
+    )]
+    Duration { duration: Duration, max: Duration },
+    /// The last shock to the collar was too recent
+    #[error(
+        "Shocks must be {}ms apart, wait another {}ms",
+        cooldown.as_millis(),
+        remaining.as_millis()
+    )]
+    Cooldown {
+        cooldown: Duration,
+        remaining: Duration,
+    },
+    /// Another shock to the collar is still queued, so the cooldown cannot be guaranteed
+    #[error("Another shock to this collar is still queued, shocks must be {}ms apart", cooldown.as_millis())]
+    ShockQueued { cooldown: Duration },
+}
+
+impl LimitError {
+    /// Stable identifier of the error for machine readable output
+    pub fn code(&self) -> &'static str {
+        match self {
+            LimitError::Intensity { .. } => "intensity_limit",
+            LimitError::Duration { .. } => "duration_limit",
+            LimitError::Cooldown { .. } | LimitError::ShockQueued { .. } => "cooldown",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    /// Test combining limits and checking intensities
+    #[test]
+    fn combines_limits() {
+        let collar = Limits {
+            max_shock: 20,
+            cooldown: Duration::from_secs(5),
+            ..Limits::NONE
+        };
+        let global = Limits {
+            max_shock: 50,
+            max_vibrate: 60,
+            max_duration: Duration::from_secs(10),
+            cooldown: Duration::from_secs(1),
+        };
+        assert_eq!(
+            collar.strictest(&global),
+            Limits {
+                max_shock: 20,
+                max_vibrate: 60,
+                max_duration: Duration::from_secs(10),
+                cooldown: Duration::from_secs(5),
+            }
+        );
+
+        let mut packet = Packet {
+            id: 1,
+            channel: Channel::Zero,
+            action: Action::Shock,
+            intensity: 21,
+        };
+        let error = collar.check_intensity(&packet).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Shock intensity 21 exceeds the limit of 20"
+        );
+        assert_eq!(error.code(), "intensity_limit");
+        packet.action = Action::Vibrate;
+        assert_eq!(collar.check_intensity(&packet), Ok(()));
+        packet.action = Action::Beep;
+        packet.intensity = 99;
+        assert_eq!(global.check_intensity(&packet), Ok(()));
+    }
+
+    /// Test that the stored format round trips and rejects garbage
+    #[test]
+    fn encodes_limits() {
+        let limits = Limits {
+            max_shock: 20,
+            max_vibrate: 30,
+            max_duration: Duration::from_millis(1500),
+            cooldown: Duration::from_secs(5),
+        };
+        assert_eq!(Limits::decode(&limits.encode()), Some(limits));
+        assert_eq!(Limits::decode(&Limits::NONE.encode()), Some(Limits::NONE));
+
+        let mut bytes = limits.encode();
+        bytes[0] = 100;
+        assert_eq!(Limits::decode(&bytes), None);
+        let mut bytes = limits.encode();
+        bytes[2..6].fill(0);
+        assert_eq!(Limits::decode(&bytes), None);
+    }
+}
@@ -0,0 +1,296 @@
+use alloc::{collections::VecDeque, string::String};
+use core::fmt::{self, Write};
+
+/// Prompt printed in front of the line being edited
+pub const PROMPT: &str = "> ";
+
+/// Longest line that can be entered
+pub const MAX_LINE_LENGTH: usize = 100;
+
+/// Amount of lines kept in the history
+pub const HISTORY_SIZE: usize = 16;
+
+/// Progress of an escape sequence sent by the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    /// Not in an escape sequence
+    None,
+    /// Got the escape character
+    Started,
+    /// Got `ESC [` or `ESC O`, waiting for the final byte
+    Sequence,
+}
+
+/// Line editor for a terminal connected to a serial port
+///
+/// Bytes typed on the terminal are fed in one at a time. Everything that has to be shown on the
+/// terminal, like the echo of typed characters, is written to the given output.
+#[derive(Debug)]
+pub struct LineEditor {
+    /// Line being edited
+    line: String,
+    /// Previously entered lines, the newest one last
+    history: VecDeque<String>,
+    /// Position in the history while browsing it, counted from the newest line
+    browsing: Option<usize>,
+    /// Line that was being edited before browsing the history
+    draft: String,
+    /// Progress of the current escape sequence
+    escape: Escape,
+    /// Whether the last byte was a carriage return, so a following line feed is skipped
+    after_return: bool,
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineEditor {
+    /// Create a new line editor with an empty history
+    pub fn new() -> Self {
+        Self {
+            line: String::new(),
+            history: VecDeque::with_capacity(HISTORY_SIZE),
+            browsing: None,
+            draft: String::new(),
+            escape: Escape::None,
+            after_return: false,
+        }
+    }
+
+    /// Line being edited
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// Previously entered lines, the newest one last
+    pub fn history(&self) -> impl Iterator<Item = &str> {
+        self.history.iter().map(String::as_str)
+    }
+
+    /// Handle a byte typed on the terminal
+    ///
+    /// Returns the line once enter is pressed.
+    pub fn push(
+        &mut self,
+        byte: u8,
+        output: &mut impl Write,
+    ) -> Result<Option<String>, fmt::Error> {
+        let after_return = core::mem::replace(&mut self.after_return, false);
+
+        match self.escape {
+            Escape::Started => {
+                self.escape = match byte {
+                    b'[' | b'O' => Escape::Sequence,
+                    _ => Escape::None,
+                };
+                return Ok(None);
+            }
+            Escape::Sequence => {
+                // parameters like the 3 in `ESC [ 3 ~` are skipped until the final byte
+                if !(0x40..=0x7e).contains(&byte) {
+                    return Ok(None);
+                }
+                self.escape = Escape::None;
+                match byte {
+                    b'A' => self.browse_older(output)?,
+                    b'B' => self.browse_newer(output)?,
+                    _ => {}
+                }
+                return Ok(None);
+            }
+            Escape::None => {}
+        }
+
+        match byte {
+            // escape
+            0x1b => self.escape = Escape::Started,
+            // line feed directly after a carriage return
+            b'\n' if after_return => {}
+            b'\r' | b'\n' => {
+                self.after_return = byte == b'\r';
+                output.write_str("\r\n")?;
+                return Ok(Some(self.submit()));
+            }
+            // backspace and delete
+            0x08 | 0x7f if self.line.pop().is_some() => output.write_str("\x08 \x08")?,
+            // ctrl-c
+            0x03 => {
+                self.line.clear();
+                self.browsing = None;
+                output.write_str("^C\r\n")?;
+                output.write_str(PROMPT)?;
+            }
+            b'\t' | b' '..=b'~' => {
+                if self.line.len() >= MAX_LINE_LENGTH {
+                    // bell
+                    output.write_char('\x07')?;
+                } else {
+                    let char = if byte == b'\t' { ' ' } else { char::from(byte) };
+                    self.line.push(char);
+                    output.write_char(char)?;
+                }
+            }
+            // other control characters and non-ASCII bytes are ignored
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    /// Print the prompt and the line being edited again, after other output was printed
+    pub fn redraw(&self, output: &mut impl Write) -> fmt::Result {
+        output.write_str(PROMPT)?;
+        output.write_str(&self.line)
+    }
+
+    /// Finish the line being edited and add it to the history
+    fn submit(&mut self) -> String {
+        let line = core::mem::take(&mut self.line);
+        self.browsing = None;
+        self.draft.clear();
+
+        let is_repeat = self.history.back().is_some_and(|last| *last == line);
+        if !line.trim().is_empty() && !is_repeat {
+            if self.history.len() == HISTORY_SIZE {
+                self.history.pop_front();
+            }
+            self.history.push_back(line.clone());
+        }
+        line
+    }
+
+    /// Replace the line with the previous line in the history
+    fn browse_older(&mut self, output: &mut impl Write) -> fmt::Result {
+        let index = self.browsing.map_or(0, |index| index + 1);
+        if index >= self.history.len() {
+            return Ok(());
+        }
+        if self.browsing.is_none() {
+            self.draft = core::mem::take(&mut self.line);
+        }
+        self.browsing = Some(index);
+        self.line = self.history[self.history.len() - 1 - index].clone();
+        self.replace_line(output)
+    }
+
+    /// Replace the line with the next line in the history, or the draft after the newest one
+    fn browse_newer(&mut self, output: &mut impl Write) -> fmt::Result {
+        match self.browsing {
+            None => return Ok(()),
+            Some(0) => {
+                self.browsing = None;
+                self.line = core::mem::take(&mut self.draft);
+            }
+            Some(index) => {
+                self.browsing = Some(index - 1);
+                self.line = self.history[self.history.len() - index].clone();
+            }
+        }
+        self.replace_line(output)
+    }
+
+    /// Clear the terminal line and print the line being edited
+    fn replace_line(&self, output: &mut impl Write) -> fmt::Result {
+        output.write_str("\r\x1b[K")?;
+        self.redraw(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec::Vec};
+
+    /// Feed a string into the editor, returning the entered lines and the output
+    fn feed(editor: &mut LineEditor, input: &str) -> (Vec<String>, String) {
+        let mut output = String::new();
+        let lines = input
+            .bytes()
+            .filter_map(|byte| editor.push(byte, &mut output).unwrap())
+            .collect();
+        (lines, output)
+    }
+
+    /// Test that typed characters are echoed and lines are returned on enter
+    #[test]
+    fn echoes_and_returns_lines() {
+        let mut editor = LineEditor::new();
+        let (lines, output) = feed(&mut editor, "id 1\r\nshock\nbeep\r");
+        assert_eq!(lines, ["id 1", "shock", "beep"]);
+        assert_eq!(output, "id 1\r\nshock\r\nbeep\r\n");
+        assert_eq!(editor.line(), "");
+
+        // the line feed of the last carriage return is skipped, empty lines are still returned
+        let (lines, _) = feed(&mut editor, "\n");
+        assert!(lines.is_empty());
+        let (lines, _) = feed(&mut editor, "\r\n");
+        assert_eq!(lines, [""]);
+    }
+
+    /// Test that backspace and delete remove the last character
+    #[test]
+    fn erases_characters() {
+        let mut editor = LineEditor::new();
+        let (lines, output) = feed(&mut editor, "shocl\x08k\x7f\x7f\x7f\x7f\x7f\x7fid\n");
+        assert_eq!(lines, ["id"]);
+        assert_eq!(
+            output,
+            "shocl\x08 \x08k\x08 \x08\x08 \x08\x08 \x08\x08 \x08\x08 \x08id\r\n"
+        );
+    }
+
+    /// Test that ctrl-c drops the line
+    #[test]
+    fn clears_line() {
+        let mut editor = LineEditor::new();
+        let (lines, output) = feed(&mut editor, "shock 99\x03beep\n");
+        assert_eq!(lines, ["beep"]);
+        assert_eq!(output, "shock 99^C\r\n> beep\r\n");
+    }
+
+    /// Test that lines longer than the limit are cut off with a bell
+    #[test]
+    fn limits_line_length() {
+        let mut editor = LineEditor::new();
+        let input = "a".repeat(MAX_LINE_LENGTH + 2) + "\n";
+        let (lines, output) = feed(&mut editor, &input);
+        assert_eq!(lines, ["a".repeat(MAX_LINE_LENGTH)]);
+        assert!(output.ends_with("\x07\x07\r\n"));
+    }
+
+    /// Test browsing the history with the arrow keys
+    #[test]
+    fn browses_history() {
+        let mut editor = LineEditor::new();
+        feed(&mut editor, "id 1\nid 1\nbeep\n");
+        assert_eq!(editor.history().collect::<Vec<_>>(), ["id 1", "beep"]);
+
+        // up twice, then down once
+        let (lines, output) = feed(&mut editor, "sh\x1b[A\x1b[A\x1b[A\x1b[B");
+        assert!(lines.is_empty());
+        assert_eq!(editor.line(), "beep");
+        assert!(output.ends_with("\r\x1b[K> beep"));
+
+        // down past the newest line restores the draft
+        feed(&mut editor, "\x1b[B");
+        assert_eq!(editor.line(), "sh");
+
+        // unknown sequences are ignored, application mode arrows work as well
+        let (lines, _) = feed(&mut editor, "\x1b[3~\x1bOAock\n");
+        assert_eq!(lines, ["beepock"]);
+    }
+
+    /// Test that the history only keeps the newest lines
+    #[test]
+    fn drops_old_history() {
+        let mut editor = LineEditor::new();
+        for index in 0..HISTORY_SIZE + 3 {
+            feed(&mut editor, &(index.to_string() + "\n"));
+        }
+        let history: Vec<_> = editor.history().collect();
+        assert_eq!(history.len(), HISTORY_SIZE);
+        assert_eq!(history[0], "3");
+    }
+}
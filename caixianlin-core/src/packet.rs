@@ -0,0 +1,319 @@
+use alloc::vec::Vec;
+
+use crate::{
+    frame::Frame,
+    protocol::{CaiXianLin, Protocol},
+};
+
+/// Channels one can send a message on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Channel {
+    Zero,
+    One,
+    Two,
+}
+
+/// Error returned when converting an unknown value into a Channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("invalid channel {0}")]
+pub struct InvalidChannel(pub u8);
+
+impl TryFrom<u8> for Channel {
+    type Error = InvalidChannel;
+
+    /// Convert a u8 to a Channel
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Channel::Zero),
+            1 => Ok(Channel::One),
+            2 => Ok(Channel::Two),
+            _ => Err(InvalidChannel(value)),
+        }
+    }
+}
+
+impl TryFrom<[bool; 4]> for Channel {
+    type Error = InvalidChannel;
+
+    /// Convert the bits of a channel nibble to a Channel
+    fn try_from(bits: [bool; 4]) -> Result<Self, Self::Error> {
+        Channel::try_from(nibble_to_u8(bits))
+    }
+}
+
+impl From<&Channel> for [bool; 4] {
+    /// Convert a Channel to a vector of bits
+    fn from(channel: &Channel) -> Self {
+        match channel {
+            Channel::Zero => [false, false, false, false],
+            Channel::One => [false, false, false, true],
+            Channel::Two => [false, false, true, false],
+        }
+    }
+}
+
+/// Actions one can perform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Action {
+    /// Shock the collar
+    Shock = 1,
+    /// Vibrate the collar
+    Vibrate = 2,
+    /// Beep the collar
+    Beep = 3,
+    /// Toggle the light on the collar
+    Light = 4,
+}
+
+impl Action {
+    /// Name of the action
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Shock => "shock",
+            Action::Vibrate => "vibrate",
+            Action::Beep => "beep",
+            Action::Light => "light",
+        }
+    }
+}
+
+/// Error returned when converting an unknown value into an Action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("invalid action {0}")]
+pub struct InvalidAction(pub u8);
+
+impl TryFrom<u8> for Action {
+    type Error = InvalidAction;
+
+    /// Convert a u8 to an Action
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Action::Shock),
+            2 => Ok(Action::Vibrate),
+            3 => Ok(Action::Beep),
+            4 => Ok(Action::Light),
+            _ => Err(InvalidAction(value)),
+        }
+    }
+}
+
+impl TryFrom<[bool; 4]> for Action {
+    type Error = InvalidAction;
+
+    /// Convert the bits of a mode nibble to an Action
+    fn try_from(bits: [bool; 4]) -> Result<Self, Self::Error> {
+        Action::try_from(nibble_to_u8(bits))
+    }
+}
+
+/// Convert a nibble of bits (most significant bit first) to a u8
+fn nibble_to_u8(bits: [bool; 4]) -> u8 {
+    bits.iter().fold(0, |acc, &x| (acc << 1) + x as u8)
+}
+
+impl From<&Action> for [bool; 4] {
+    /// Convert an Action to a vector of bits
+    fn from(action: &Action) -> Self {
+        match action {
+            Action::Shock => [false, false, false, true],
+            Action::Vibrate => [false, false, true, false],
+            Action::Beep => [false, false, true, true],
+            Action::Light => [false, true, false, false],
+        }
+    }
+}
+
+/// Errors that can occur while decoding a packet
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DecodeError {
+    /// The frame does not have the expected amount of bits
+    #[error("expected {expected} bits, got {actual}")]
+    InvalidLength { expected: usize, actual: usize },
+    /// The channel nibble does not map to a known channel
+    #[error(transparent)]
+    InvalidChannel(#[from] InvalidChannel),
+    /// The mode nibble does not map to a known action
+    #[error(transparent)]
+    InvalidAction(#[from] InvalidAction),
+    /// The checksum does not match the data
+    #[error("checksum mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: u8, actual: u8 },
+    /// The end bits are not all zero
+    #[error("end bits are not zero")]
+    InvalidEndBits,
+}
+
+/// Packet to send to the collar
+///
+/// The layout of the packet on the air depends on the [`Protocol`] it is encoded with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    /// ID of the collar
+    pub id: u16,
+    /// Channel to send the message on
+    pub channel: Channel,
+    /// Action to perform
+    pub action: Action,
+    /// Intensity of the action (0-99)
+    pub intensity: u8,
+}
+
+impl Packet {
+    /// Decode a CaiXianLin packet from a vector of bits
+    pub fn new(bits: &[bool]) -> Result<Self, DecodeError> {
+        let frame = Frame::try_from(bits).map_err(|_| DecodeError::InvalidLength {
+            expected: CaiXianLin.frame_len(),
+            actual: bits.len(),
+        })?;
+        Self::from_frame(&frame)
+    }
+
+    /// Decode a CaiXianLin packet from a frame
+    pub fn from_frame(frame: &Frame) -> Result<Self, DecodeError> {
+        CaiXianLin.decode(frame)
+    }
+
+    /// Encode the packet into a CaiXianLin frame
+    pub fn to_frame(&self) -> Frame {
+        CaiXianLin
+            .encode(self)
+            .expect("CaiXianLin supports all packets")
+    }
+
+    /// Encode the packet into a CaiXianLin vector of bits
+    pub fn to_bits(&self) -> Vec<bool> {
+        self.to_frame().into()
+    }
+}
+
+impl From<&Packet> for Frame {
+    /// Convert a Packet to a CaiXianLin frame
+    fn from(packet: &Packet) -> Self {
+        packet.to_frame()
+    }
+}
+
+impl TryFrom<Frame> for Packet {
+    type Error = DecodeError;
+
+    /// Convert a CaiXianLin frame to a Packet
+    fn try_from(frame: Frame) -> Result<Self, Self::Error> {
+        Packet::from_frame(&frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Convert a string of ones and zeros into a vector of bits, ignoring whitespace
+    fn string_to_vec(string: &str) -> Vec<bool> {
+        string
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c == '1')
+            .collect()
+    }
+
+    /// Test encoding and decoding of packets
+    #[test]
+    fn test_packet() {
+        let packet = Packet {
+            id: 0b1111010101010000,
+            channel: Channel::Zero,
+            action: Action::Shock,
+            intensity: 0b10101010,
+        };
+        let bits = packet.to_bits();
+        let packet2 = Packet::new(&bits).unwrap();
+        let bits2 = packet2.to_bits();
+        assert_eq!(bits, bits2);
+    }
+
+    /// Test decoding of packets
+    #[test]
+    fn decodes_known_packet() {
+        let decoded = string_to_vec("00101100 10111110 00000010 00110010 00011110 000");
+        let packet = Packet::new(&decoded).unwrap();
+        assert_eq!(packet.id, 0b0010110010111110);
+        assert_eq!(packet.intensity, 0b00110010);
+    }
+
+    /// Test reencoding of decoded packets
+    #[test]
+    fn reencodes_known_packet() {
+        let decoded = string_to_vec("00101100 10111110 00000010 00110010 00011110 000");
+        let packet = Packet::new(&decoded).unwrap();
+        let reencoded = packet.to_bits();
+        assert_eq!(decoded, reencoded);
+    }
+
+    /// Test decoding of packets against known values
+    #[test]
+    fn decodes_known_packet_to_the_expected_value() {
+        let decoded = string_to_vec("00101100 10111110 00000010 00110010 00011110 000");
+        let packet = Packet {
+            id: 0b0010110010111110,
+            channel: Channel::Zero,
+            action: Action::Vibrate,
+            intensity: 0b00110010,
+        };
+        let generated_packet = packet.to_bits();
+        assert_eq!(decoded, generated_packet);
+    }
+
+    /// Test that a corrupted checksum is rejected
+    #[test]
+    fn rejects_invalid_checksum() {
+        let decoded = string_to_vec("00101100 10111110 00000010 00110010 00011111 000");
+        assert_eq!(
+            Packet::new(&decoded).unwrap_err(),
+            DecodeError::ChecksumMismatch {
+                expected: 0b00011110,
+                actual: 0b00011111
+            }
+        );
+    }
+
+    /// Test that non-zero end bits are rejected
+    #[test]
+    fn rejects_invalid_end_bits() {
+        let decoded = string_to_vec("00101100 10111110 00000010 00110010 00011110 010");
+        assert_eq!(
+            Packet::new(&decoded).unwrap_err(),
+            DecodeError::InvalidEndBits
+        );
+    }
+
+    /// Test that unknown channel and action values are rejected
+    #[test]
+    fn rejects_invalid_enums() {
+        assert_eq!(Channel::try_from(3), Err(InvalidChannel(3)));
+        assert_eq!(Action::try_from(0), Err(InvalidAction(0)));
+        assert_eq!(
+            Action::try_from([false, true, false, false]),
+            Ok(Action::Light)
+        );
+
+        let decoded = string_to_vec("00101100 10111110 00110010 00110010 01001110 000");
+        assert_eq!(
+            Packet::new(&decoded).unwrap_err(),
+            DecodeError::InvalidChannel(InvalidChannel(3))
+        );
+    }
+
+    /// Test that frames of the wrong length are rejected
+    #[test]
+    fn rejects_invalid_length() {
+        let decoded = string_to_vec("00101100 10111110 00000010 00110010 00011110");
+        assert_eq!(
+            Packet::new(&decoded).unwrap_err(),
+            DecodeError::InvalidLength {
+                expected: 43,
+                actual: 40
+            }
+        );
+    }
+}
@@ -0,0 +1,490 @@
+use alloc::string::{String, ToString};
+use core::{str::FromStr, time::Duration};
+
+use crate::{
+    frame::Frame,
+    packet::{Action, Channel, DecodeError, InvalidAction, InvalidChannel, Packet},
+};
+
+/// Durations of the high and low part of a symbol in microseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    /// Duration of the high pulse
+    pub high: u16,
+    /// Duration of the low pulse
+    pub low: u16,
+}
+
+impl Timing {
+    /// Create a new timing
+    pub const fn new(high: u16, low: u16) -> Self {
+        Self { high, low }
+    }
+
+    /// Check whether measured pulse durations match this timing
+    ///
+    /// The tolerance is given in percent of the expected durations.
+    /// A low duration of zero matches any low duration, as the last pulse of a capture has no
+    /// measurable end.
+    pub fn matches(&self, high: u16, low: u16, tolerance: u8) -> bool {
+        let within = |expected: u16, actual: u16| {
+            let margin = expected as u32 * tolerance as u32 / 100;
+            (actual as u32).abs_diff(expected as u32) <= margin
+        };
+        within(self.high, high) && (low == 0 || within(self.low, low))
+    }
+}
+
+/// Symbols a protocol is made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symbol {
+    /// Sync symbol at the start of a frame
+    Sync,
+    /// One bit
+    One,
+    /// Zero bit
+    Zero,
+}
+
+/// Pulse timings of a protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timings {
+    /// Sync symbol sent before every frame
+    pub sync: Timing,
+    /// Symbol for a one bit
+    pub one: Timing,
+    /// Symbol for a zero bit
+    pub zero: Timing,
+}
+
+impl Timings {
+    /// Time it takes to transmit a frame, including the sync symbol
+    pub fn airtime(&self, frame: &Frame) -> Duration {
+        let micros: u64 = self
+            .symbols(frame)
+            .map(|timing| timing.high as u64 + timing.low as u64)
+            .sum();
+        Duration::from_micros(micros)
+    }
+
+    /// Timings of the symbols that make up the transmission of a frame, starting with the sync
+    pub fn symbols(&self, frame: &Frame) -> impl Iterator<Item = Timing> {
+        let timings = *self;
+        core::iter::once(timings.sync).chain(frame.iter().map(move |bit| {
+            if bit {
+                timings.one
+            } else {
+                timings.zero
+            }
+        }))
+    }
+
+    /// Classify measured pulse durations as one of the symbols
+    pub fn classify(&self, high: u16, low: u16, tolerance: u8) -> Option<Symbol> {
+        if self.sync.matches(high, low, tolerance) {
+            Some(Symbol::Sync)
+        } else if self.one.matches(high, low, tolerance) {
+            Some(Symbol::One)
+        } else if self.zero.matches(high, low, tolerance) {
+            Some(Symbol::Zero)
+        } else {
+            None
+        }
+    }
+}
+
+/// Errors that can occur while encoding a packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum EncodeError {
+    /// The protocol has no representation for the channel
+    #[error("channel {0:?} is not supported by this protocol")]
+    UnsupportedChannel(Channel),
+}
+
+/// A family of 433 MHz OOK collars
+///
+/// Every frame consists of the data bits, followed by the checksum and the end bits.
+/// Each frame is preceded by a sync symbol when it is transmitted.
+pub trait Protocol {
+    /// Name used to select the protocol
+    fn name(&self) -> &'static str;
+
+    /// Pulse timings of the protocol
+    fn timings(&self) -> Timings;
+
+    /// Amount of data bits in a frame
+    fn data_len(&self) -> usize;
+
+    /// Amount of checksum bits in a frame
+    fn checksum_len(&self) -> usize;
+
+    /// Bits appended to every frame
+    fn end_bits(&self) -> Frame;
+
+    /// Encode a packet into the data bits of a frame
+    fn encode_data(&self, packet: &Packet) -> Result<Frame, EncodeError>;
+
+    /// Decode the data bits of a frame into a packet
+    fn decode_data(&self, data: &Frame) -> Result<Packet, DecodeError>;
+
+    /// Calculate the checksum of the data bits
+    fn checksum(&self, data: &Frame) -> u8;
+
+    /// Total amount of bits in a frame
+    fn frame_len(&self) -> usize {
+        self.data_len() + self.checksum_len() + self.end_bits().len()
+    }
+
+    /// Encode a packet into a complete frame
+    fn encode(&self, packet: &Packet) -> Result<Frame, EncodeError> {
+        let mut frame = self.encode_data(packet)?;
+        frame.push_bits(self.checksum(&frame) as u64, self.checksum_len());
+        frame.extend(self.end_bits());
+        Ok(frame)
+    }
+
+    /// Decode and validate a complete frame
+    fn decode(&self, frame: &Frame) -> Result<Packet, DecodeError> {
+        if frame.len() != self.frame_len() {
+            return Err(DecodeError::InvalidLength {
+                expected: self.frame_len(),
+                actual: frame.len(),
+            });
+        }
+
+        let data = frame.slice(0, self.data_len());
+        let expected = self.checksum(&data);
+        let actual = frame.field(self.data_len(), self.checksum_len()) as u8;
+        if expected != actual {
+            return Err(DecodeError::ChecksumMismatch { expected, actual });
+        }
+
+        let end_bits = self.end_bits();
+        let end = frame.slice(self.data_len() + self.checksum_len(), end_bits.len());
+        if end != end_bits {
+            return Err(DecodeError::InvalidEndBits);
+        }
+
+        self.decode_data(&data)
+    }
+}
+
+/// Protocol of the CaiXianLin collars
+///
+/// Each frame is 43 bits in length:
+/// [PREFIX        ] = SYNC
+/// [TRANSMITTER ID] =     XXXXXXXXXXXXXXXX
+/// [CHANNEL       ] =                     XXXX
+/// [MODE          ] =                         XXXX
+/// [STRENGTH      ] =                             XXXXXXXX
+/// [CHECKSUM      ] =                                     XXXXXXXX
+/// [END           ] =                                             000
+#[derive(Debug, Clone, Copy)]
+pub struct CaiXianLin;
+
+impl Protocol for CaiXianLin {
+    fn name(&self) -> &'static str {
+        "caixianlin"
+    }
+
+    fn timings(&self) -> Timings {
+        Timings {
+            sync: Timing::new(1400, 800),
+            one: Timing::new(800, 300),
+            zero: Timing::new(300, 800),
+        }
+    }
+
+    fn data_len(&self) -> usize {
+        32
+    }
+
+    fn checksum_len(&self) -> usize {
+        8
+    }
+
+    fn end_bits(&self) -> Frame {
+        let mut frame = Frame::new();
+        frame.push_bits(0, 3);
+        frame
+    }
+
+    fn encode_data(&self, packet: &Packet) -> Result<Frame, EncodeError> {
+        let mut frame = Frame::new();
+
+        // id
+        frame.push_bits(packet.id as u64, 16);
+
+        let channel: [bool; 4] = (&packet.channel).into();
+        frame.extend(channel);
+
+        let action: [bool; 4] = (&packet.action).into();
+        frame.extend(action);
+
+        // intensity
+        frame.push_bits(packet.intensity as u64, 8);
+
+        Ok(frame)
+    }
+
+    fn decode_data(&self, data: &Frame) -> Result<Packet, DecodeError> {
+        Ok(Packet {
+            id: data.field(0, 16) as u16,
+            channel: Channel::try_from(data.field(16, 4) as u8)?,
+            action: Action::try_from(data.field(20, 4) as u8)?,
+            intensity: data.field(24, 8) as u8,
+        })
+    }
+
+    /// The checksum is the sum of the four data bytes, truncated to eight bits.
+    fn checksum(&self, data: &Frame) -> u8 {
+        (data.field(0, 32) as u32)
+            .to_be_bytes()
+            .iter()
+            .fold(0u8, |acc, &byte| acc.wrapping_add(byte))
+    }
+}
+
+/// Protocol of the Petrainer-style collars
+///
+/// Each frame is 42 bits in length:
+/// [PREFIX        ] = SYNC
+/// [CHANNEL       ] =     XXXX
+/// [MODE          ] =         XXXX
+/// [TRANSMITTER ID] =             XXXXXXXXXXXXXXXX
+/// [STRENGTH      ] =                             XXXXXXXX
+/// [MODE INVERTED ] =                                     XXXX
+/// [CHAN INVERTED ] =                                         XXXX
+/// [END           ] =                                             00
+///
+/// The inverted fields are the mode and channel nibbles reversed and inverted.
+/// These collars only know two channels.
+#[derive(Debug, Clone, Copy)]
+pub struct Petrainer;
+
+impl Petrainer {
+    /// Reverse and invert a nibble
+    fn reverse_invert(nibble: u8) -> u8 {
+        !(nibble.reverse_bits() >> 4) & 0x0F
+    }
+}
+
+impl Protocol for Petrainer {
+    fn name(&self) -> &'static str {
+        "petrainer"
+    }
+
+    fn timings(&self) -> Timings {
+        Timings {
+            sync: Timing::new(1500, 750),
+            one: Timing::new(750, 250),
+            zero: Timing::new(250, 750),
+        }
+    }
+
+    fn data_len(&self) -> usize {
+        32
+    }
+
+    fn checksum_len(&self) -> usize {
+        8
+    }
+
+    fn end_bits(&self) -> Frame {
+        let mut frame = Frame::new();
+        frame.push_bits(0, 2);
+        frame
+    }
+
+    fn encode_data(&self, packet: &Packet) -> Result<Frame, EncodeError> {
+        let channel = match packet.channel {
+            Channel::Zero => 0b1000,
+            Channel::One => 0b1111,
+            Channel::Two => return Err(EncodeError::UnsupportedChannel(packet.channel)),
+        };
+        let action = match packet.action {
+            Action::Shock => 0b0001,
+            Action::Vibrate => 0b0010,
+            Action::Beep => 0b0100,
+            Action::Light => 0b1000,
+        };
+
+        let mut frame = Frame::new();
+        frame.push_bits(channel, 4);
+        frame.push_bits(action, 4);
+        frame.push_bits(packet.id as u64, 16);
+        frame.push_bits(packet.intensity as u64, 8);
+        Ok(frame)
+    }
+
+    fn decode_data(&self, data: &Frame) -> Result<Packet, DecodeError> {
+        let channel = match data.field(0, 4) as u8 {
+            0b1000 => Channel::Zero,
+            0b1111 => Channel::One,
+            value => return Err(InvalidChannel(value).into()),
+        };
+        let action = match data.field(4, 4) as u8 {
+            0b0001 => Action::Shock,
+            0b0010 => Action::Vibrate,
+            0b0100 => Action::Beep,
+            0b1000 => Action::Light,
+            value => return Err(InvalidAction(value).into()),
+        };
+
+        Ok(Packet {
+            id: data.field(8, 16) as u16,
+            channel,
+            action,
+            intensity: data.field(24, 8) as u8,
+        })
+    }
+
+    /// The checksum repeats the mode and channel nibbles, reversed and inverted.
+    fn checksum(&self, data: &Frame) -> u8 {
+        let channel = data.field(0, 4) as u8;
+        let action = data.field(4, 4) as u8;
+        (Self::reverse_invert(action) << 4) | Self::reverse_invert(channel)
+    }
+}
+
+/// Error returned when a protocol name or number is unknown
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown protocol {0}")]
+pub struct InvalidProtocol(pub String);
+
+/// Protocols that can be selected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ProtocolKind {
+    /// CaiXianLin collars
+    CaiXianLin = 0,
+    /// Petrainer-style collars
+    Petrainer = 1,
+}
+
+impl ProtocolKind {
+    /// All selectable protocols
+    pub const ALL: [ProtocolKind; 2] = [ProtocolKind::CaiXianLin, ProtocolKind::Petrainer];
+
+    /// Get the implementation of the protocol
+    pub fn protocol(&self) -> &'static dyn Protocol {
+        match self {
+            ProtocolKind::CaiXianLin => &CaiXianLin,
+            ProtocolKind::Petrainer => &Petrainer,
+        }
+    }
+
+    /// Name of the protocol
+    pub fn name(&self) -> &'static str {
+        self.protocol().name()
+    }
+}
+
+impl TryFrom<u8> for ProtocolKind {
+    type Error = InvalidProtocol;
+
+    /// Convert a u8 to a ProtocolKind
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        ProtocolKind::ALL
+            .into_iter()
+            .find(|kind| *kind as u8 == value)
+            .ok_or_else(|| InvalidProtocol(value.to_string()))
+    }
+}
+
+impl FromStr for ProtocolKind {
+    type Err = InvalidProtocol;
+
+    /// Look up a protocol by its name
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        ProtocolKind::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| InvalidProtocol(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that every protocol decodes what it encodes
+    #[test]
+    fn round_trips_all_protocols() {
+        let packet = Packet {
+            id: 0xBEEF,
+            channel: Channel::One,
+            action: Action::Beep,
+            intensity: 42,
+        };
+        for kind in ProtocolKind::ALL {
+            let protocol = kind.protocol();
+            let frame = protocol.encode(&packet).unwrap();
+            assert_eq!(frame.len(), protocol.frame_len());
+            assert_eq!(protocol.decode(&frame).unwrap(), packet);
+        }
+    }
+
+    /// Test the Petrainer layout against a hand-assembled frame
+    #[test]
+    fn encodes_petrainer_frame() {
+        let packet = Packet {
+            id: 0x1234,
+            channel: Channel::Zero,
+            action: Action::Vibrate,
+            intensity: 10,
+        };
+        let mut expected = Frame::new();
+        expected.push_bits(0b1000_0010, 8);
+        expected.push_bits(0x1234, 16);
+        expected.push_bits(10, 8);
+        expected.push_bits(0b1011_1110, 8);
+        expected.push_bits(0, 2);
+        assert_eq!(Petrainer.encode(&packet).unwrap(), expected);
+    }
+
+    /// Test that channels a protocol cannot represent are rejected
+    #[test]
+    fn rejects_unsupported_channel() {
+        let packet = Packet {
+            id: 1,
+            channel: Channel::Two,
+            action: Action::Shock,
+            intensity: 1,
+        };
+        assert_eq!(
+            Petrainer.encode(&packet),
+            Err(EncodeError::UnsupportedChannel(Channel::Two))
+        );
+    }
+
+    /// Test calculating the airtime of a frame
+    #[test]
+    fn calculates_airtime() {
+        let mut frame = Frame::new();
+        frame.push_bits(0b101, 3);
+        // sync, one, zero, one
+        assert_eq!(
+            CaiXianLin.timings().airtime(&frame),
+            Duration::from_micros(2200 + 1100 + 1100 + 1100)
+        );
+    }
+
+    /// Test classifying measured pulses with a tolerance
+    #[test]
+    fn classifies_pulses() {
+        let timings = CaiXianLin.timings();
+        assert_eq!(timings.classify(1450, 760, 20), Some(Symbol::Sync));
+        assert_eq!(timings.classify(820, 280, 20), Some(Symbol::One));
+        assert_eq!(timings.classify(290, 0, 20), Some(Symbol::Zero));
+        assert_eq!(timings.classify(550, 550, 20), None);
+    }
+
+    /// Test looking up protocols by name and number
+    #[test]
+    fn looks_up_protocols() {
+        assert_eq!("Petrainer".parse(), Ok(ProtocolKind::Petrainer));
+        assert_eq!(ProtocolKind::try_from(0), Ok(ProtocolKind::CaiXianLin));
+        assert!("dogtra".parse::<ProtocolKind>().is_err());
+    }
+}
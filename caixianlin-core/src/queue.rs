@@ -0,0 +1,1005 @@
+use alloc::{collections::VecDeque, vec::Vec};
+use core::time::Duration;
+
+use crate::{
+    frame::Frame,
+    limits::{LimitError, Limits, Target, MAX_COOLDOWN},
+    packet::{Action, Packet},
+    protocol::{EncodeError, ProtocolKind, Timing, Timings},
+    sequence::Step,
+    transmitter::Transmitter,
+    warning::Warning,
+};
+
+/// How often a frame is transmitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    /// Transmit the frame the given amount of times
+    Count(u16),
+    /// Keep transmitting the frame for the given time
+    ///
+    /// The time starts with the first transmission. No transmission is started that would end
+    /// after the time is up, except for the first one.
+    For(Duration),
+}
+
+/// Reported by [`Queue::tick`] once the last frame of a job was transmitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Finished {
+    /// Amount of jobs left in the queue, the queue is drained if this is zero
+    pub remaining: usize,
+}
+
+/// Reasons the queue refuses a transmission
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum SendError {
+    /// The packet cannot be encoded with the protocol
+    #[error("Cannot encode packet: {0}")]
+    Encode(#[from] EncodeError),
+    /// The transmission violates the safety limits
+    #[error(transparent)]
+    Limit(#[from] LimitError),
+}
+
+impl SendError {
+    /// Stable identifier of the error for machine readable output
+    pub fn code(&self) -> &'static str {
+        match self {
+            SendError::Encode(_) => "encode_failed",
+            SendError::Limit(error) => error.code(),
+        }
+    }
+}
+
+/// A frame that should be transmitted repeatedly
+#[derive(Debug, Clone, Copy)]
+struct Job {
+    /// Collar the frame is addressed to
+    target: Target,
+    /// Action the frame triggers
+    action: Action,
+    /// Frame to transmit
+    frame: Frame,
+    /// Pulse timings of the protocol the frame was encoded with
+    timings: Timings,
+    /// Remaining transmissions
+    repeat: Repeat,
+    /// Time to wait before the first frame, once the job is up next
+    pause: Duration,
+    /// Earliest start of the first frame, set once the job is up next and has to pause
+    start: Option<Duration>,
+    /// End of the transmission, set once the first frame was transmitted
+    deadline: Option<Duration>,
+}
+
+impl Job {
+    /// Time the job still transmits, without its pause
+    fn remaining(&self, now: Duration) -> Duration {
+        match (self.repeat, self.deadline) {
+            (Repeat::Count(amount), _) => self.timings.airtime(&self.frame) * amount as u32,
+            (Repeat::For(duration), None) => duration,
+            (Repeat::For(_), Some(deadline)) => deadline.saturating_sub(now),
+        }
+    }
+}
+
+/// Jobs of a transmission that passed the limits
+struct Plan {
+    /// Warning burst that precedes a shock
+    warning: Option<Job>,
+    /// The transmission itself
+    job: Job,
+    /// Length of the transmission without gaps that ends with the job
+    continuous: Duration,
+}
+
+/// Queue of frames waiting to be transmitted
+pub struct Queue<T: Transmitter> {
+    /// Jobs in the order they were sent, the first one is being transmitted
+    jobs: VecDeque<Job>,
+    /// Transmitter the frames are sent to
+    transmitter: T,
+    /// Buffer for the symbols of the frame being transmitted
+    symbols: Vec<Timing>,
+    /// Whether the frame being transmitted is the last one of its job
+    finishing: bool,
+    /// Limits of all transmissions
+    limits: Limits,
+    /// Additional limits of single collars
+    collar_limits: Vec<(Target, Limits)>,
+    /// Warnings that precede shocks to single collars
+    warnings: Vec<(Target, Warning)>,
+    /// End of the latest shock to each collar, for the cooldown
+    last_shocks: Vec<(Target, Duration)>,
+    /// Time of the last tick
+    now: Duration,
+}
+
+impl<T: Transmitter> Queue<T> {
+    /// Create a new queue sending to the given transmitter
+    pub fn new(transmitter: T) -> Self {
+        Self {
+            jobs: VecDeque::new(),
+            transmitter,
+            symbols: Vec::new(),
+            finishing: false,
+            limits: Limits::NONE,
+            collar_limits: Vec::new(),
+            warnings: Vec::new(),
+            last_shocks: Vec::new(),
+            now: Duration::ZERO,
+        }
+    }
+
+    /// Send a packet repeatedly, encoded with the given protocol.
+    ///
+    /// The transmission is refused if it violates the limits of its collar. Queued jobs to the
+    /// same collar that it directly follows count towards the maximum duration. The cooldown
+    /// between shocks is measured from the time of the last tick.
+    ///
+    /// A shock to a collar with a warning is preceded by the warning burst and its delay. Both are
+    /// queued together, so nothing is transmitted in between.
+    pub fn send(
+        &mut self,
+        protocol: ProtocolKind,
+        packet: &Packet,
+        repeat: Repeat,
+    ) -> Result<(), SendError> {
+        let before = self.queued_run(&Target::new(protocol, packet));
+        let plan = self.plan(protocol, packet, repeat, Duration::ZERO, before, true)?;
+        if repeat == Repeat::Count(0) || repeat == Repeat::For(Duration::ZERO) {
+            return Ok(());
+        }
+        self.jobs.extend(plan.warning);
+        self.jobs.push_back(plan.job);
+        Ok(())
+    }
+
+    /// Send the steps of a sequence to a collar, one after another with their pauses in between
+    ///
+    /// Every step is checked like in [`Queue::send`], steps without a pause or warning delay between
+    /// count as one transmission for the maximum duration, together with the queued jobs the first
+    /// step directly follows. Only the first shock of the sequence is warned of, so ramps stay
+    /// smooth. Shocks within the sequence have to be apart by the cooldown, unless they follow
+    /// each other without a gap and form one longer shock. Either all steps are queued or none,
+    /// and nothing is transmitted in between. The pauses are measured from the planned end of the
+    /// previous step.
+    pub fn send_sequence(&mut self, target: Target, steps: &[Step]) -> Result<(), SendError> {
+        let limits = self.limits_for(&target);
+        let mut jobs = Vec::with_capacity(steps.len());
+        let mut pause = Duration::ZERO;
+        // planned time of the step and the end of the last shock, relative to the first step
+        let mut time = Duration::ZERO;
+        let mut last_shock: Option<Duration> = None;
+        // length of the transmission without gaps the step belongs to
+        let mut continuous = self.queued_run(&target);
+        let mut warned = false;
+        for step in steps {
+            let packet = step.packet(&target);
+            let repeat = Repeat::For(step.duration);
+            let plan = self.plan(target.protocol, &packet, repeat, pause, continuous, !warned)?;
+            continuous = plan.continuous;
+            warned |= plan.warning.is_some();
+            time += pause;
+            if let Some(warning) = self.warning_for(&target).filter(|_| plan.warning.is_some()) {
+                time += warning.duration + warning.delay;
+            }
+            if step.action == Action::Shock {
+                let cooldown = limits.cooldown;
+                // a shock right at the end of the previous one continues it, like in a ramp
+                let apart = last_shock.filter(|end| *end != time);
+                if let Some(end) = apart.filter(|end| time < *end + cooldown) {
+                    return Err(LimitError::Cooldown {
+                        cooldown,
+                        remaining: end + cooldown - time,
+                    }
+                    .into());
+                }
+                last_shock = Some(time + step.duration);
+            }
+            time += step.duration;
+            pause = step.delay;
+            jobs.extend(plan.warning);
+            jobs.push(plan.job);
+        }
+        self.jobs.extend(jobs);
+        Ok(())
+    }
+
+    /// Check a transmission and build its jobs, the warning burst first if there is one
+    ///
+    /// `pause` is waited before the first frame of the transmission, including its warning.
+    /// `before` is the length of the transmission without gaps that it follows if there is no
+    /// pause, it counts towards the maximum duration. A shock is only warned of if `warn` is set.
+    fn plan(
+        &self,
+        protocol: ProtocolKind,
+        packet: &Packet,
+        repeat: Repeat,
+        pause: Duration,
+        before: Duration,
+        warn: bool,
+    ) -> Result<Plan, SendError> {
+        let frame = protocol.protocol().encode(packet)?;
+        let timings = protocol.protocol().timings();
+        let target = Target::new(protocol, packet);
+        let limits = self.limits_for(&target);
+        self.check(target, packet)?;
+
+        let job = Job {
+            target,
+            action: packet.action,
+            frame,
+            timings,
+            repeat,
+            pause,
+            start: None,
+            deadline: None,
+        };
+        let mut continuous = if pause.is_zero() {
+            before
+        } else {
+            Duration::ZERO
+        };
+        // the warning has to respect the limits as well
+        let (warning, job) = match self.warning_for(&target) {
+            Some(warning) if warn && packet.action == Action::Shock => {
+                let warning_packet = warning.packet(packet);
+                limits.check_intensity(&warning_packet)?;
+                let warning_job = Job {
+                    action: warning.action,
+                    frame: protocol.protocol().encode(&warning_packet)?,
+                    repeat: Repeat::For(warning.duration),
+                    ..job
+                };
+                let job = Job {
+                    pause: warning.delay,
+                    ..job
+                };
+                // the burst runs into the transmission unless there is a delay in between
+                continuous += warning.duration;
+                if !warning.delay.is_zero() {
+                    continuous = Duration::ZERO;
+                }
+                (Some(warning_job), job)
+            }
+            _ => (None, job),
+        };
+
+        continuous += job.remaining(self.now);
+        if continuous > limits.max_duration {
+            return Err(LimitError::Duration {
+                duration: continuous,
+                max: limits.max_duration,
+            }
+            .into());
+        }
+        Ok(Plan {
+            warning,
+            job,
+            continuous,
+        })
+    }
+
+    /// Length of the queued transmission to a collar without gaps that a new job would follow
+    fn queued_run(&self, target: &Target) -> Duration {
+        let mut run = Duration::ZERO;
+        for job in self.jobs.iter().rev() {
+            if job.target != *target {
+                break;
+            }
+            run += job.remaining(self.now);
+            if !job.pause.is_zero() {
+                break;
+            }
+        }
+        run
+    }
+
+    /// Check the intensity and cooldown of a transmission against the limits of its collar
+    fn check(&self, target: Target, packet: &Packet) -> Result<(), LimitError> {
+        let limits = self.limits_for(&target);
+        limits.check_intensity(packet)?;
+
+        if packet.action != Action::Shock || limits.cooldown.is_zero() {
+            return Ok(());
+        }
+        // the start of a queued shock is not known yet
+        let queued = self
+            .jobs
+            .iter()
+            .any(|job| job.target == target && job.action == Action::Shock);
+        if queued {
+            return Err(LimitError::ShockQueued {
+                cooldown: limits.cooldown,
+            });
+        }
+        let last_shock = self
+            .last_shocks
+            .iter()
+            .find(|(shocked, _)| *shocked == target);
+        if let Some((_, end)) = last_shock {
+            let ready = *end + limits.cooldown;
+            if self.now < ready {
+                return Err(LimitError::Cooldown {
+                    cooldown: limits.cooldown,
+                    remaining: ready - self.now,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the limits of all transmissions
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Limits of all transmissions
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Replace the additional limits of single collars
+    pub fn set_collar_limits(&mut self, limits: impl IntoIterator<Item = (Target, Limits)>) {
+        self.collar_limits.clear();
+        self.collar_limits.extend(limits);
+    }
+
+    /// Replace the warnings that precede shocks to single collars
+    pub fn set_warnings(&mut self, warnings: impl IntoIterator<Item = (Target, Warning)>) {
+        self.warnings.clear();
+        self.warnings.extend(warnings);
+    }
+
+    /// Warning that precedes shocks to a collar
+    pub fn warning_for(&self, target: &Target) -> Option<Warning> {
+        self.warnings
+            .iter()
+            .find(|(collar, _)| collar == target)
+            .map(|(_, warning)| *warning)
+    }
+
+    /// Limits that apply to transmissions to a collar
+    pub fn limits_for(&self, target: &Target) -> Limits {
+        self.collar_limits
+            .iter()
+            .filter(|(collar, _)| collar == target)
+            .fold(self.limits, |limits, (_, collar)| limits.strictest(collar))
+    }
+
+    /// Tick the transmitter.
+    ///
+    /// Starts the next transmission once the transmitter is idle. `now` is the time since an
+    /// arbitrary but fixed point, like the boot of the device. Returns whether a job was finished
+    /// since the last tick.
+    pub fn tick(&mut self, now: Duration) -> Option<Finished> {
+        self.now = now;
+
+        // skip if transmitting
+        if self.transmitter.is_busy() {
+            return None;
+        }
+
+        // the last frame of a counted job is done once the transmitter is idle again
+        let mut finished = core::mem::take(&mut self.finishing);
+
+        // pauses start when the previous job was planned to end, or now if it ended later
+        let mut ended = now;
+
+        // get the current job, skipping jobs whose time is up
+        let job = loop {
+            let Some(job) = self.jobs.front_mut() else {
+                return finished.then_some(Finished { remaining: 0 });
+            };
+            // wait before the first frame, like between a warning and its shock
+            if !job.pause.is_zero() {
+                let start = *job.start.get_or_insert(ended + job.pause);
+                if now < start {
+                    return finished.then_some(Finished {
+                        remaining: self.jobs.len(),
+                    });
+                }
+                job.pause = Duration::ZERO;
+            }
+            let airtime = job.timings.airtime(&job.frame);
+            match (job.repeat, job.deadline) {
+                (Repeat::Count(repeats), _) => {
+                    job.repeat = Repeat::Count(repeats - 1);
+                    let job = *job;
+                    if repeats == 1 {
+                        self.jobs.pop_front();
+                        self.finishing = true;
+                    }
+                    break job;
+                }
+                (Repeat::For(duration), None) => {
+                    job.deadline = Some(now + duration);
+                    break *job;
+                }
+                (Repeat::For(_), Some(deadline)) if now + airtime <= deadline => break *job,
+                (Repeat::For(_), Some(deadline)) => {
+                    self.jobs.pop_front();
+                    finished = true;
+                    ended = deadline.max(now);
+                }
+            }
+        };
+
+        // remember when the collar was shocked last
+        if job.action == Action::Shock {
+            let end = now + job.timings.airtime(&job.frame);
+            self.last_shocks
+                .retain(|(target, shocked)| *target != job.target && now < *shocked + MAX_COOLDOWN);
+            self.last_shocks.push((job.target, end));
+        }
+
+        // transmit the frame
+        self.symbols.clear();
+        self.symbols.extend(job.timings.symbols(&job.frame));
+        self.transmitter.transmit(&self.symbols);
+
+        // the job being transmitted is still pending
+        finished.then(|| Finished {
+            remaining: self.jobs.len() + usize::from(self.finishing),
+        })
+    }
+
+    /// Drop all queued frames and abort the transmission in progress
+    pub fn stop(&mut self) {
+        self.jobs.clear();
+        self.finishing = false;
+        self.transmitter.abort();
+    }
+
+    /// Whether no frames are waiting to be transmitted
+    ///
+    /// A timed job that has no time left for another frame only counts as done after the next
+    /// tick.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Amount of jobs waiting to be transmitted, including the one in progress
+    pub fn len(&self) -> usize {
+        self.jobs.len() + usize::from(self.finishing)
+    }
+
+    /// Transmitter the frames are sent to
+    pub fn transmitter(&self) -> &T {
+        &self.transmitter
+    }
+
+    /// Mutable access to the transmitter the frames are sent to
+    pub fn transmitter_mut(&mut self) -> &mut T {
+        &mut self.transmitter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        packet::{Action, Channel, Packet},
+        protocol::{CaiXianLin, Protocol},
+        transmitter::RecordingTransmitter,
+    };
+
+    fn packet(intensity: u8) -> Packet {
+        Packet {
+            id: 1234,
+            channel: Channel::One,
+            action: Action::Vibrate,
+            intensity,
+        }
+    }
+
+    fn frame(intensity: u8) -> Frame {
+        CaiXianLin.encode(&packet(intensity)).unwrap()
+    }
+
+    /// Queue a vibration with the given intensity
+    fn send(queue: &mut Queue<RecordingTransmitter>, intensity: u8, repeat: Repeat) {
+        queue
+            .send(ProtocolKind::CaiXianLin, &packet(intensity), repeat)
+            .unwrap();
+    }
+
+    /// Tick the queue until it is drained, finishing every transmission after its airtime
+    ///
+    /// Pauses are waited out in steps of a millisecond.
+    ///
+    /// Returns the times at which the transmissions were started.
+    fn drain(queue: &mut Queue<RecordingTransmitter>) -> Vec<Duration> {
+        drain_with_events(queue).0
+    }
+
+    /// Like [`drain`], but also returns the finished jobs with the index of the frame before
+    fn drain_with_events(
+        queue: &mut Queue<RecordingTransmitter>,
+    ) -> (Vec<Duration>, Vec<(usize, Finished)>) {
+        let mut now = Duration::ZERO;
+        let mut starts = Vec::new();
+        let mut events = Vec::new();
+        loop {
+            if let Some(finished) = queue.tick(now) {
+                events.push((starts.len(), finished));
+            }
+            if !queue.transmitter().is_busy() {
+                if queue.is_empty() {
+                    return (starts, events);
+                }
+                // the next job is pausing
+                now += Duration::from_millis(1);
+                continue;
+            }
+            starts.push(now);
+            let symbols = queue.transmitter().transmissions.last().unwrap();
+            let airtime: u64 = symbols.iter().map(|t| t.high as u64 + t.low as u64).sum();
+            now += Duration::from_micros(airtime);
+            queue.transmitter_mut().finish();
+        }
+    }
+
+    /// Test that the queue emits the exact pulse timings of the frame
+    #[test]
+    fn emits_frame_timings() {
+        let timings = CaiXianLin.timings();
+        let mut queue = Queue::new(RecordingTransmitter::new());
+        send(&mut queue, 10, Repeat::Count(1));
+        drain(&mut queue);
+
+        let expected: Vec<Timing> = timings.symbols(&frame(10)).collect();
+        assert_eq!(queue.transmitter().transmissions, vec![expected]);
+        assert_eq!(queue.transmitter().transmissions[0].len(), 44);
+        assert_eq!(
+            queue.transmitter().transmissions[0][0],
+            Timing::new(1400, 800)
+        );
+    }
+
+    /// Test that frames are repeated and sent in order
+    #[test]
+    fn repeats_frames_in_order() {
+        let timings = CaiXianLin.timings();
+        let mut queue = Queue::new(RecordingTransmitter::new());
+        send(&mut queue, 1, Repeat::Count(3));
+        send(&mut queue, 2, Repeat::Count(0));
+        send(&mut queue, 3, Repeat::Count(2));
+        drain(&mut queue);
+
+        let expected: Vec<Vec<Timing>> = [1, 1, 1, 3, 3]
+            .into_iter()
+            .map(|intensity| timings.symbols(&frame(intensity)).collect())
+            .collect();
+        assert_eq!(queue.transmitter().transmissions, expected);
+    }
+
+    /// Test that nothing is started while the transmitter is busy
+    #[test]
+    fn waits_for_transmitter() {
+        let mut queue = Queue::new(RecordingTransmitter::new());
+        send(&mut queue, 1, Repeat::Count(2));
+
+        queue.tick(Duration::ZERO);
+        queue.tick(Duration::ZERO);
+        queue.tick(Duration::ZERO);
+        assert_eq!(queue.transmitter().transmissions.len(), 1);
+
+        queue.transmitter_mut().finish();
+        queue.tick(Duration::ZERO);
+        assert_eq!(queue.transmitter().transmissions.len(), 2);
+    }
+
+    /// Test that stopping drops everything and aborts the frame in flight
+    #[test]
+    fn stops_immediately() {
+        let mut queue = Queue::new(RecordingTransmitter::new());
+        send(&mut queue, 1, Repeat::Count(1000));
+        send(&mut queue, 2, Repeat::Count(1000));
+        queue.tick(Duration::ZERO);
+
+        queue.stop();
+        assert!(queue.is_empty());
+        assert!(!queue.transmitter().is_busy());
+        assert_eq!(queue.transmitter().aborted, 1);
+
+        drain(&mut queue);
+        assert_eq!(queue.transmitter().transmissions.len(), 1);
+    }
+
+    /// Test that timed jobs repeat until their time is up
+    #[test]
+    fn repeats_for_duration() {
+        let timings = CaiXianLin.timings();
+        let airtime = timings.airtime(&frame(1));
+        let mut queue = Queue::new(RecordingTransmitter::new());
+        send(&mut queue, 1, Repeat::Count(1));
+        send(&mut queue, 2, Repeat::For(airtime * 3 + airtime / 2));
+        send(&mut queue, 3, Repeat::Count(1));
+        let starts = drain(&mut queue);
+
+        // the timed job starts after the first frame and fits three frames
+        let expected: Vec<Duration> = (0..5).map(|index| airtime * index).collect();
+        assert_eq!(starts, expected);
+        let expected: Vec<Vec<Timing>> = [1, 2, 2, 2, 3]
+            .into_iter()
+            .map(|intensity| timings.symbols(&frame(intensity)).collect())
+            .collect();
+        assert_eq!(queue.transmitter().transmissions, expected);
+    }
+
+    /// Test that a timed job transmits at least once
+    #[test]
+    fn transmits_short_duration_once() {
+        let mut queue = Queue::new(RecordingTransmitter::new());
+        send(&mut queue, 1, Repeat::For(Duration::from_millis(1)));
+        drain(&mut queue);
+        assert_eq!(queue.transmitter().transmissions.len(), 1);
+        assert!(queue.is_empty());
+    }
+
+    /// Test that finished jobs are reported once their last frame is done
+    #[test]
+    fn reports_finished_jobs() {
+        let timings = CaiXianLin.timings();
+        let airtime = timings.airtime(&frame(1));
+        let mut queue = Queue::new(RecordingTransmitter::new());
+        send(&mut queue, 1, Repeat::Count(2));
+        send(&mut queue, 2, Repeat::For(airtime * 2));
+        send(&mut queue, 3, Repeat::Count(1));
+        assert_eq!(queue.len(), 3);
+
+        let (starts, events) = drain_with_events(&mut queue);
+        assert_eq!(starts.len(), 5);
+        assert_eq!(
+            events,
+            [
+                (2, Finished { remaining: 2 }),
+                (4, Finished { remaining: 1 }),
+                (5, Finished { remaining: 0 }),
+            ]
+        );
+        assert!(queue.is_empty());
+    }
+
+    /// Test that transmissions violating the limits are refused
+    #[test]
+    fn enforces_limits() {
+        let airtime = CaiXianLin.timings().airtime(&frame(1));
+        let mut queue = Queue::new(RecordingTransmitter::new());
+        queue.set_limits(Limits {
+            max_vibrate: 50,
+            max_duration: airtime * 4,
+            ..Limits::NONE
+        });
+        let shock = Packet {
+            action: Action::Shock,
+            ..packet(30)
+        };
+        let target = Target::new(ProtocolKind::CaiXianLin, &shock);
+        queue.set_collar_limits([(
+            target,
+            Limits {
+                max_shock: 30,
+                cooldown: Duration::from_secs(5),
+                ..Limits::NONE
+            },
+        )]);
+
+        let error = queue.send(ProtocolKind::CaiXianLin, &packet(51), Repeat::Count(1));
+        assert_eq!(error.unwrap_err().code(), "intensity_limit");
+        let error = queue.send(ProtocolKind::CaiXianLin, &packet(50), Repeat::Count(5));
+        assert_eq!(error.unwrap_err().code(), "duration_limit");
+        let error = queue.send(
+            ProtocolKind::CaiXianLin,
+            &packet(1),
+            Repeat::For(airtime * 5),
+        );
+        assert_eq!(error.unwrap_err().code(), "duration_limit");
+        let error = queue.send(
+            ProtocolKind::CaiXianLin,
+            &Packet {
+                intensity: 31,
+                ..shock
+            },
+            Repeat::Count(1),
+        );
+        assert_eq!(error.unwrap_err().code(), "intensity_limit");
+        assert!(queue.is_empty());
+
+        // shocks to the same collar need to be apart
+        queue
+            .send(ProtocolKind::CaiXianLin, &shock, Repeat::Count(1))
+            .unwrap();
+        let error = queue.send(ProtocolKind::CaiXianLin, &shock, Repeat::Count(1));
+        assert!(matches!(
+            error,
+            Err(SendError::Limit(LimitError::ShockQueued { .. }))
+        ));
+        drain(&mut queue);
+        let error = queue.send(ProtocolKind::CaiXianLin, &shock, Repeat::Count(1));
+        assert!(matches!(
+            error,
+            Err(SendError::Limit(LimitError::Cooldown { .. }))
+        ));
+        queue.tick(airtime + Duration::from_secs(5));
+        queue
+            .send(ProtocolKind::CaiXianLin, &shock, Repeat::Count(1))
+            .unwrap();
+
+        // other collars have their own cooldown
+        let other = Packet { id: 1, ..shock };
+        queue
+            .send(ProtocolKind::CaiXianLin, &other, Repeat::Count(1))
+            .unwrap();
+        queue
+            .send(ProtocolKind::CaiXianLin, &other, Repeat::Count(1))
+            .unwrap();
+    }
+
+    /// Test that queued jobs to a collar that run back to back count as one transmission
+    #[test]
+    fn limits_continuous_transmissions() {
+        let mut queue = Queue::new(RecordingTransmitter::new());
+        queue.set_limits(Limits {
+            max_duration: Duration::from_secs(10),
+            ..Limits::NONE
+        });
+        let seconds = |seconds| Repeat::For(Duration::from_secs(seconds));
+        send(&mut queue, 10, seconds(6));
+        let error = queue.send(ProtocolKind::CaiXianLin, &packet(10), seconds(5));
+        assert_eq!(
+            error,
+            Err(SendError::Limit(LimitError::Duration {
+                duration: Duration::from_secs(11),
+                max: Duration::from_secs(10),
+            }))
+        );
+        send(&mut queue, 10, seconds(4));
+
+        // another collar in between is a gap
+        let other = Packet {
+            id: 1,
+            ..packet(10)
+        };
+        queue
+            .send(ProtocolKind::CaiXianLin, &other, seconds(6))
+            .unwrap();
+        send(&mut queue, 10, seconds(6));
+
+        // only the remaining time of the job in progress counts
+        let mut queue = Queue::new(RecordingTransmitter::new());
+        queue.set_limits(Limits {
+            max_duration: Duration::from_secs(10),
+            ..Limits::NONE
+        });
+        send(&mut queue, 10, seconds(6));
+        queue.tick(Duration::ZERO);
+        queue.tick(Duration::from_secs(3));
+        send(&mut queue, 10, seconds(7));
+        let error = queue.send(ProtocolKind::CaiXianLin, &packet(10), seconds(1));
+        assert_eq!(error.unwrap_err().code(), "duration_limit");
+    }
+
+    /// Test that shocks to a collar with a warning are preceded by the burst and the delay
+    #[test]
+    fn warns_before_shocks() {
+        let timings = CaiXianLin.timings();
+        let shock = Packet {
+            action: Action::Shock,
+            ..packet(30)
+        };
+        let warning = Warning {
+            action: Action::Vibrate,
+            intensity: 20,
+            duration: Duration::from_millis(100),
+            delay: Duration::from_millis(500),
+        };
+        let mut queue = Queue::new(RecordingTransmitter::new());
+        queue.set_warnings([(Target::new(ProtocolKind::CaiXianLin, &shock), warning)]);
+
+        // other actions and collars are not warned of
+        send(&mut queue, 10, Repeat::Count(1));
+        let other = Packet { id: 1, ..shock };
+        queue
+            .send(ProtocolKind::CaiXianLin, &other, Repeat::Count(1))
+            .unwrap();
+        queue
+            .send(ProtocolKind::CaiXianLin, &shock, Repeat::Count(2))
+            .unwrap();
+        assert_eq!(queue.len(), 4);
+        let starts = drain(&mut queue);
+
+        let vibrate = CaiXianLin.encode(&packet(20)).unwrap();
+        let shock = CaiXianLin.encode(&shock).unwrap();
+        let warned =
+            timings.airtime(&frame(10)) + timings.airtime(&CaiXianLin.encode(&other).unwrap());
+        // the delay starts when the warning was planned to end, not with its last frame
+        let warning_end = warned + warning.duration;
+        let expected = [
+            Duration::ZERO,
+            timings.airtime(&frame(10)),
+            warned,
+            warned + timings.airtime(&vibrate),
+            warning_end + warning.delay,
+            warning_end + warning.delay + timings.airtime(&shock),
+        ];
+        assert_eq!(starts, expected);
+        let transmissions = &queue.transmitter().transmissions;
+        assert_eq!(
+            transmissions[3],
+            timings.symbols(&vibrate).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            transmissions[4],
+            timings.symbols(&shock).collect::<Vec<_>>()
+        );
+
+        // the warning has to respect the limits
+        queue.set_limits(Limits {
+            max_vibrate: 10,
+            ..Limits::NONE
+        });
+        let error = queue.send(
+            ProtocolKind::CaiXianLin,
+            &Packet {
+                action: Action::Shock,
+                ..packet(30)
+            },
+            Repeat::Count(1),
+        );
+        assert_eq!(error.unwrap_err().code(), "intensity_limit");
+        assert!(queue.is_empty());
+
+        // a warning without delay runs into the shock and counts towards the maximum duration
+        let shock = Packet {
+            action: Action::Shock,
+            ..packet(30)
+        };
+        let target = Target::new(ProtocolKind::CaiXianLin, &shock);
+        let mut queue = Queue::new(RecordingTransmitter::new());
+        queue.set_limits(Limits {
+            max_duration: Duration::from_secs(1),
+            ..Limits::NONE
+        });
+        queue.set_warnings([(
+            target,
+            Warning {
+                delay: Duration::ZERO,
+                ..warning
+            },
+        )]);
+        let second = Repeat::For(Duration::from_secs(1));
+        let error = queue.send(ProtocolKind::CaiXianLin, &shock, second);
+        assert_eq!(
+            error,
+            Err(SendError::Limit(LimitError::Duration {
+                duration: Duration::from_millis(1100),
+                max: Duration::from_secs(1),
+            }))
+        );
+        let steps = [
+            Step {
+                action: Action::Vibrate,
+                intensity: 10,
+                duration: Duration::from_millis(500),
+                delay: Duration::ZERO,
+            },
+            Step {
+                action: Action::Shock,
+                intensity: 10,
+                duration: Duration::from_millis(500),
+                delay: Duration::ZERO,
+            },
+        ];
+        let error = queue.send_sequence(target, &steps);
+        assert_eq!(error.unwrap_err().code(), "duration_limit");
+
+        // with a delay the shock starts a new transmission
+        queue.set_warnings([(target, warning)]);
+        queue
+            .send(ProtocolKind::CaiXianLin, &shock, second)
+            .unwrap();
+    }
+
+    /// Test that sequences keep their gaps and are checked as a whole
+    #[test]
+    fn sends_sequences() {
+        let timings = CaiXianLin.timings();
+        let step = |action, intensity, duration, delay| Step {
+            action,
+            intensity,
+            duration: Duration::from_millis(duration),
+            delay: Duration::from_millis(delay),
+        };
+        let target = Target::new(ProtocolKind::CaiXianLin, &packet(0));
+        let mut queue = Queue::new(RecordingTransmitter::new());
+        queue
+            .send_sequence(
+                target,
+                &[
+                    step(Action::Vibrate, 30, 100, 300),
+                    step(Action::Beep, 0, 1, 0),
+                    step(Action::Shock, 10, 1, 0),
+                ],
+            )
+            .unwrap();
+        assert_eq!(queue.len(), 3);
+        let starts = drain(&mut queue);
+
+        // the pause starts when the vibration was planned to end
+        let airtime = |action, intensity| {
+            let packet = Packet {
+                action,
+                ..packet(intensity)
+            };
+            timings.airtime(&CaiXianLin.encode(&packet).unwrap())
+        };
+        let beep = Duration::from_millis(400);
+        let expected = [
+            Duration::ZERO,
+            airtime(Action::Vibrate, 30),
+            beep,
+            beep + airtime(Action::Beep, 0),
+        ];
+        assert_eq!(starts, expected);
+
+        // shocks within the sequence need to be apart, nothing is queued otherwise
+        let mut queue = Queue::new(RecordingTransmitter::new());
+        queue.set_collar_limits([(
+            target,
+            Limits {
+                max_vibrate: 20,
+                cooldown: Duration::from_secs(1),
+                ..Limits::NONE
+            },
+        )]);
+        let error = queue.send_sequence(
+            target,
+            &[
+                step(Action::Shock, 10, 100, 500),
+                step(Action::Beep, 0, 100, 0),
+                step(Action::Shock, 10, 100, 0),
+            ],
+        );
+        assert_eq!(
+            error,
+            Err(SendError::Limit(LimitError::Cooldown {
+                cooldown: Duration::from_secs(1),
+                remaining: Duration::from_millis(400),
+            }))
+        );
+        // shocks without a gap are one shock
+        let shocks = [
+            step(Action::Shock, 10, 100, 0),
+            step(Action::Shock, 20, 100, 0),
+        ];
+        assert_eq!(queue.send_sequence(target, &shocks), Ok(()));
+        queue.stop();
+        let error = queue.send_sequence(
+            target,
+            &[
+                step(Action::Beep, 0, 100, 0),
+                step(Action::Vibrate, 30, 100, 0),
+            ],
+        );
+        assert_eq!(error.unwrap_err().code(), "intensity_limit");
+        assert!(queue.is_empty());
+
+        // steps without pauses count as one transmission
+        queue.set_limits(Limits {
+            max_duration: Duration::from_millis(150),
+            ..Limits::NONE
+        });
+        let beeps = [step(Action::Beep, 0, 100, 0), step(Action::Beep, 0, 100, 0)];
+        let error = queue.send_sequence(target, &beeps);
+        assert_eq!(error.unwrap_err().code(), "duration_limit");
+        let beeps = [
+            step(Action::Beep, 0, 100, 50),
+            step(Action::Beep, 0, 100, 0),
+        ];
+        queue.send_sequence(target, &beeps).unwrap();
+        assert_eq!(queue.len(), 2);
+    }
+}
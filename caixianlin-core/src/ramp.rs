@@ -0,0 +1,196 @@
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use crate::{packet::Action, sequence::Step};
+
+/// Shortest time a ramp stays at one intensity, about two frames
+pub const MIN_RAMP_STEP: Duration = Duration::from_millis(100);
+
+/// Most plateaus of a stepped ramp
+pub const MAX_PLATEAUS: u8 = 20;
+
+/// Plateaus of a stepped ramp if none are given
+pub const DEFAULT_PLATEAUS: u8 = 4;
+
+/// Steepness of the exponential curve, the last tenth of the time covers about a quarter of the
+/// intensity range
+const EXPONENT: f32 = 3.0;
+
+/// How the intensity moves from the start to the end of a ramp
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Curve {
+    /// Change the intensity evenly
+    Linear,
+    /// Change the intensity slowly at first and quickly at the end
+    Exponential,
+    /// Hold the given amount of evenly spaced intensities for the same time each
+    Stepped(u8),
+}
+
+impl Curve {
+    /// Name of the curve
+    pub fn name(&self) -> &'static str {
+        match self {
+            Curve::Linear => "linear",
+            Curve::Exponential => "exponential",
+            Curve::Stepped(_) => "stepped",
+        }
+    }
+
+    /// Part of the intensity range covered after the given part of the ramp, both from 0 to 1
+    fn progress(&self, x: f32) -> f32 {
+        match self {
+            Curve::Linear | Curve::Stepped(_) => x,
+            Curve::Exponential => (exp(EXPONENT * x) - 1.0) / (exp(EXPONENT) - 1.0),
+        }
+    }
+}
+
+/// Intensity that changes over time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ramp {
+    /// Action to perform, shock or vibrate
+    pub action: Action,
+    /// Intensity at the start
+    pub from: u8,
+    /// Intensity at the end
+    pub to: u8,
+    /// Time from the start to the end
+    pub duration: Duration,
+    /// How the intensity moves
+    pub curve: Curve,
+}
+
+impl Ramp {
+    /// Steps of a sequence that follow the ramp
+    ///
+    /// Smooth curves change the intensity by at most one per step, unless a step would be
+    /// shorter than [`MIN_RAMP_STEP`]. Steps with the same intensity are merged.
+    pub fn steps(&self) -> Vec<Step> {
+        let amount = match self.curve {
+            Curve::Stepped(plateaus) => plateaus as u32,
+            Curve::Linear | Curve::Exponential => {
+                let levels = self.from.abs_diff(self.to) as u32 + 1;
+                let fitting = (self.duration.as_millis() / MIN_RAMP_STEP.as_millis()) as u32;
+                levels.min(fitting)
+            }
+        }
+        .max(1);
+
+        let mut steps: Vec<Step> = Vec::new();
+        let mut start = Duration::ZERO;
+        for index in 0..amount {
+            let end = self.duration * (index + 1) / amount;
+            let x = match amount {
+                1 => 0.0,
+                _ => index as f32 / (amount - 1) as f32,
+            };
+            let range = self.to as f32 - self.from as f32;
+            // rounding half up, the result is within 0 to 99
+            let intensity = (self.from as f32 + range * self.curve.progress(x) + 0.5) as u8;
+            match steps.last_mut() {
+                Some(step) if step.intensity == intensity => step.duration += end - start,
+                _ => steps.push(Step {
+                    action: self.action,
+                    intensity,
+                    duration: end - start,
+                    delay: Duration::ZERO,
+                }),
+            }
+            start = end;
+        }
+        steps
+    }
+}
+
+impl Display for Ramp {
+    /// Describe the ramp in a sentence
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} from {} to {} over {}ms, {}",
+            self.action.name(),
+            self.from,
+            self.to,
+            self.duration.as_millis(),
+            self.curve.name()
+        )
+    }
+}
+
+/// e to the power of x, precise enough for the curves
+///
+/// `core` has no floating point functions, so this sums the Taylor series.
+fn exp(x: f32) -> f32 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    for n in 1..30 {
+        term *= x / n as f32;
+        sum += term;
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp(from: u8, to: u8, millis: u64, curve: Curve) -> Ramp {
+        Ramp {
+            action: Action::Vibrate,
+            from,
+            to,
+            duration: Duration::from_millis(millis),
+            curve,
+        }
+    }
+
+    fn intensities(steps: &[Step]) -> Vec<u8> {
+        steps.iter().map(|step| step.intensity).collect()
+    }
+
+    /// Test that the curves reach both ends and take the whole time
+    #[test]
+    fn follows_curves() {
+        let steps = ramp(10, 20, 5500, Curve::Linear).steps();
+        assert_eq!(intensities(&steps), (10..=20).collect::<Vec<_>>());
+        assert!(steps
+            .iter()
+            .all(|step| step.duration == Duration::from_millis(500)));
+
+        let steps = ramp(60, 0, 4000, Curve::Stepped(4)).steps();
+        assert_eq!(intensities(&steps), [60, 40, 20, 0]);
+        assert_eq!(steps[0].duration, Duration::from_secs(1));
+
+        // slow at first, fast at the end
+        let steps = ramp(0, 90, 10000, Curve::Exponential).steps();
+        let total: Duration = steps.iter().map(|step| step.duration).sum();
+        assert_eq!(total, Duration::from_secs(10));
+        assert_eq!(steps.first().unwrap().intensity, 0);
+        assert_eq!(steps.last().unwrap().intensity, 90);
+        let halfway = steps
+            .iter()
+            .scan(Duration::ZERO, |time, step| {
+                *time += step.duration;
+                Some((*time, step.intensity))
+            })
+            .find(|(time, _)| *time >= Duration::from_secs(5))
+            .unwrap();
+        assert!(halfway.1 < 20, "{:?}", halfway);
+    }
+
+    /// Test that short ramps use fewer steps
+    #[test]
+    fn limits_steps() {
+        let steps = ramp(0, 99, 1000, Curve::Linear).steps();
+        assert_eq!(intensities(&steps), [0, 11, 22, 33, 44, 55, 66, 77, 88, 99]);
+        let steps = ramp(30, 60, 50, Curve::Linear).steps();
+        assert_eq!(intensities(&steps), [30]);
+        let steps = ramp(30, 30, 2000, Curve::Exponential).steps();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].duration, Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,339 @@
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use crate::{
+    limits::{LimitError, Limits, Target},
+    packet::{Action, Channel, Packet},
+    queue::Repeat,
+};
+
+/// Longest random session
+pub const MAX_SESSION: Duration = Duration::from_secs(4 * 3600);
+
+/// Shortest gap between two random actions
+pub const MIN_GAP: Duration = Duration::from_secs(1);
+
+/// Longest gap between two random actions
+pub const MAX_GAP: Duration = Duration::from_secs(3600);
+
+/// Longest random action
+pub const MAX_BURST: Duration = Duration::from_secs(10);
+
+/// Small seeded random number generator, xorshift32
+///
+/// The same seed always gives the same numbers, so sessions can be reproduced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    /// Create a generator from a seed, any seed including zero is fine
+    pub fn new(seed: u32) -> Self {
+        // xorshift never leaves zero, and similar seeds should not give similar numbers
+        let state = seed.wrapping_mul(0x9e37_79b9) ^ 0x6d2b_79f5;
+        Self {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    /// Next random number
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// Random number between `min` and `max`, both included
+    pub fn range(&mut self, min: u32, max: u32) -> u32 {
+        let span = (max - min) as u64 + 1;
+        min + ((self.next_u32() as u64 * span) >> 32) as u32
+    }
+
+    /// Random duration between `min` and `max` with millisecond resolution
+    pub fn duration(&mut self, min: Duration, max: Duration) -> Duration {
+        let millis = self.range(min.as_millis() as u32, max.as_millis() as u32);
+        Duration::from_millis(millis as u64)
+    }
+}
+
+/// Bounds of a random session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RandomSettings {
+    /// Actions to choose from
+    pub actions: Vec<Action>,
+    /// Lowest intensity of shock and vibrate
+    pub min_intensity: u8,
+    /// Highest intensity of shock and vibrate
+    pub max_intensity: u8,
+    /// Shortest time between the end of an action and the start of the next one
+    pub min_gap: Duration,
+    /// Longest time between the end of an action and the start of the next one
+    pub max_gap: Duration,
+    /// How long each action lasts
+    pub burst: Duration,
+    /// How long the session lasts
+    pub session: Duration,
+    /// Seed of the random numbers, picked when the session starts if not given
+    pub seed: Option<u32>,
+}
+
+impl Default for RandomSettings {
+    fn default() -> Self {
+        Self {
+            actions: alloc::vec![Action::Vibrate, Action::Beep],
+            min_intensity: 10,
+            max_intensity: 30,
+            min_gap: Duration::from_secs(10),
+            max_gap: Duration::from_secs(60),
+            burst: Duration::from_millis(500),
+            session: Duration::from_secs(600),
+            seed: None,
+        }
+    }
+}
+
+impl RandomSettings {
+    /// Check that the strongest and longest possible action is within the limits
+    pub fn check(&self, limits: &Limits) -> Result<(), LimitError> {
+        for &action in &self.actions {
+            limits.check_intensity(&Packet {
+                id: 0,
+                channel: Channel::Zero,
+                action,
+                intensity: self.max_intensity,
+            })?;
+        }
+        if self.burst > limits.max_duration {
+            return Err(LimitError::Duration {
+                duration: self.burst,
+                max: limits.max_duration,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Display for RandomSettings {
+    /// Describe the bounds in a sentence
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, action) in self.actions.iter().enumerate() {
+            f.write_str(if index > 0 { "," } else { "" })?;
+            f.write_str(action.name())?;
+        }
+        write!(
+            f,
+            " at intensity {}-{} for {}ms every {}-{}ms",
+            self.min_intensity,
+            self.max_intensity,
+            self.burst.as_millis(),
+            self.min_gap.as_millis(),
+            self.max_gap.as_millis()
+        )
+    }
+}
+
+/// What a random session wants to do at a tick
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Poll {
+    /// Nothing to do yet
+    Idle,
+    /// Transmit the packet
+    Fire(Packet, Repeat),
+    /// The session is over
+    Finished,
+}
+
+/// Random actions within bounds, fired at random times until the session ends
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RandomSession {
+    /// Bounds of the actions
+    settings: RandomSettings,
+    /// Collar the actions are sent to
+    target: Target,
+    /// Seed the generator was created with
+    seed: u32,
+    /// Generator of all random choices
+    rng: Rng,
+    /// Time of the next action
+    next: Duration,
+    /// End of the session
+    end: Duration,
+    /// Amount of actions fired
+    fired: u32,
+}
+
+impl RandomSession {
+    /// Start a session at the given time, the first action follows after a random gap
+    pub fn new(settings: RandomSettings, target: Target, seed: u32, now: Duration) -> Self {
+        let mut rng = Rng::new(seed);
+        let next = now + rng.duration(settings.min_gap, settings.max_gap);
+        Self {
+            end: now + settings.session,
+            settings,
+            target,
+            seed,
+            rng,
+            next,
+            fired: 0,
+        }
+    }
+
+    /// Decide what to do at the given time
+    pub fn poll(&mut self, now: Duration) -> Poll {
+        if now >= self.end {
+            return Poll::Finished;
+        }
+        if now < self.next {
+            return Poll::Idle;
+        }
+        let actions = &self.settings.actions;
+        let action = actions[self.rng.range(0, actions.len() as u32 - 1) as usize];
+        let intensity = match action {
+            Action::Shock | Action::Vibrate => self.rng.range(
+                self.settings.min_intensity as u32,
+                self.settings.max_intensity as u32,
+            ) as u8,
+            Action::Beep | Action::Light => 0,
+        };
+        let gap = self
+            .rng
+            .duration(self.settings.min_gap, self.settings.max_gap);
+        self.next = now + self.settings.burst + gap;
+        self.fired += 1;
+        let packet = Packet {
+            id: self.target.id,
+            channel: self.target.channel,
+            action,
+            intensity,
+        };
+        Poll::Fire(packet, Repeat::For(self.settings.burst))
+    }
+
+    /// Bounds of the actions
+    pub fn settings(&self) -> &RandomSettings {
+        &self.settings
+    }
+
+    /// Collar the actions are sent to
+    pub fn target(&self) -> Target {
+        self.target
+    }
+
+    /// Seed to reproduce the session with
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// Amount of actions fired so far
+    pub fn fired(&self) -> u32 {
+        self.fired
+    }
+
+    /// Time until the session ends
+    pub fn remaining(&self, now: Duration) -> Duration {
+        self.end.saturating_sub(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::ProtocolKind;
+
+    /// Test that the generator stays within bounds and depends on the seed
+    #[test]
+    fn generates_numbers() {
+        let mut rng = Rng::new(0);
+        let numbers: Vec<u32> = (0..1000).map(|_| rng.range(10, 20)).collect();
+        assert!(numbers.iter().all(|number| (10..=20).contains(number)));
+        assert!(numbers.contains(&10) && numbers.contains(&20));
+
+        let sample = |seed| {
+            let mut rng = Rng::new(seed);
+            (0..4).map(|_| rng.next_u32()).collect::<Vec<_>>()
+        };
+        assert_eq!(sample(42), sample(42));
+        assert_ne!(sample(42), sample(43));
+    }
+
+    /// Test that sessions fire within bounds and can be reproduced
+    #[test]
+    fn fires_within_bounds() {
+        let settings = RandomSettings {
+            actions: alloc::vec![Action::Shock, Action::Beep],
+            min_intensity: 5,
+            max_intensity: 15,
+            min_gap: Duration::from_secs(2),
+            max_gap: Duration::from_secs(4),
+            session: Duration::from_secs(60),
+            ..RandomSettings::default()
+        };
+        let target = Target {
+            protocol: ProtocolKind::CaiXianLin,
+            id: 7,
+            channel: Channel::One,
+        };
+        let run = |seed| {
+            let mut session = RandomSession::new(settings.clone(), target, seed, Duration::ZERO);
+            let mut fired = Vec::new();
+            let mut now = Duration::ZERO;
+            loop {
+                match session.poll(now) {
+                    Poll::Idle => {}
+                    Poll::Fire(packet, repeat) => {
+                        assert_eq!(repeat, Repeat::For(Duration::from_millis(500)));
+                        fired.push((now, packet));
+                    }
+                    Poll::Finished => return fired,
+                }
+                now += Duration::from_millis(100);
+            }
+        };
+
+        let fired = run(1);
+        assert_eq!(fired, run(1));
+        assert_ne!(fired, run(2));
+        assert!((12..=30).contains(&fired.len()), "{}", fired.len());
+        for pair in fired.windows(2) {
+            let gap = pair[1].0 - pair[0].0 - Duration::from_millis(500);
+            assert!(gap >= Duration::from_secs(2) && gap <= Duration::from_millis(4100));
+        }
+        for (_, packet) in &fired {
+            assert_eq!(packet.id, 7);
+            match packet.action {
+                Action::Shock => assert!((5..=15).contains(&packet.intensity)),
+                action => assert_eq!(action, Action::Beep),
+            }
+        }
+    }
+
+    /// Test that the bounds are checked against the limits
+    #[test]
+    fn checks_limits() {
+        let limits = Limits {
+            max_vibrate: 20,
+            max_duration: Duration::from_millis(400),
+            ..Limits::NONE
+        };
+        let settings = RandomSettings::default();
+        assert_eq!(
+            settings.check(&limits).unwrap_err().code(),
+            "intensity_limit"
+        );
+        let settings = RandomSettings {
+            max_intensity: 20,
+            ..settings
+        };
+        assert_eq!(
+            settings.check(&limits).unwrap_err().code(),
+            "duration_limit"
+        );
+    }
+}
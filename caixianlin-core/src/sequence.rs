@@ -0,0 +1,307 @@
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use crate::{
+    collar::is_valid_name,
+    command::MAX_TRANSMIT_DURATION,
+    limits::Target,
+    packet::{Action, Packet},
+};
+
+/// Most steps of a sequence
+pub const MAX_STEPS: usize = 32;
+
+/// Most macros that can be saved
+pub const MAX_MACROS: usize = 16;
+
+/// Longest pause after a step
+pub const MAX_STEP_DELAY: Duration = Duration::from_secs(60);
+
+/// How long a step lasts if no duration is given
+pub const DEFAULT_STEP_DURATION: Duration = Duration::from_millis(500);
+
+/// Version of the stored macro format
+const FORMAT_VERSION: u8 = 1;
+
+/// Length of a stored step
+const ENCODED_STEP_LENGTH: usize = 10;
+
+/// One transmission of a sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    /// Action to perform
+    pub action: Action,
+    /// Intensity of the action
+    pub intensity: u8,
+    /// How long the action is repeated
+    pub duration: Duration,
+    /// Pause between the end of this step and the start of the next one
+    pub delay: Duration,
+}
+
+impl Step {
+    /// Packet of the step addressed to a collar
+    pub fn packet(&self, target: &Target) -> Packet {
+        Packet {
+            id: target.id,
+            channel: target.channel,
+            action: self.action,
+            intensity: self.intensity,
+        }
+    }
+}
+
+impl Display for Step {
+    /// Write the step the way it is entered
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.action.name())?;
+        if matches!(self.action, Action::Shock | Action::Vibrate) {
+            write!(f, " {}", self.intensity)?;
+        }
+        write!(f, " {}ms", self.duration.as_millis())?;
+        if !self.delay.is_zero() {
+            write!(f, " pause {}ms", self.delay.as_millis())?;
+        }
+        Ok(())
+    }
+}
+
+/// Time from the start of the first step until the end of the last one
+pub fn total_duration(steps: &[Step]) -> Duration {
+    let pauses = steps.split_last().map_or(Duration::ZERO, |(_, steps)| {
+        steps.iter().map(|step| step.delay).sum()
+    });
+    steps.iter().map(|step| step.duration).sum::<Duration>() + pauses
+}
+
+/// A sequence saved under a name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Macro {
+    /// Name of the macro
+    pub name: String,
+    /// Steps in the order they are transmitted
+    pub steps: Vec<Step>,
+}
+
+impl Display for Macro {
+    /// Write the steps the way they are entered
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, step) in self.steps.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+/// Errors of the macro book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum MacroBookError {
+    /// No more macros fit into the book
+    #[error("Too many macros, delete one first")]
+    Full,
+    /// The stored macros cannot be read
+    #[error("Stored macros are corrupted")]
+    Corrupted,
+}
+
+/// Macros saved under their names
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MacroBook {
+    /// Macros in the order they were defined
+    macros: Vec<Macro>,
+}
+
+impl MacroBook {
+    /// Create an empty macro book
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look up a macro by name
+    pub fn get(&self, name: &str) -> Option<&Macro> {
+        self.macros.iter().find(|entry| entry.name == name)
+    }
+
+    /// Add a macro, replacing the one with the same name
+    pub fn insert(&mut self, entry: Macro) -> Result<(), MacroBookError> {
+        if let Some(existing) = self.macros.iter_mut().find(|m| m.name == entry.name) {
+            *existing = entry;
+            return Ok(());
+        }
+        if self.macros.len() >= MAX_MACROS {
+            return Err(MacroBookError::Full);
+        }
+        self.macros.push(entry);
+        Ok(())
+    }
+
+    /// Remove a macro by name, returning it
+    pub fn remove(&mut self, name: &str) -> Option<Macro> {
+        let index = self.macros.iter().position(|entry| entry.name == name)?;
+        Some(self.macros.remove(index))
+    }
+
+    /// All macros in the order they were defined
+    pub fn iter(&self) -> impl Iterator<Item = &Macro> {
+        self.macros.iter()
+    }
+
+    /// Amount of macros
+    pub fn len(&self) -> usize {
+        self.macros.len()
+    }
+
+    /// Whether there are no macros
+    pub fn is_empty(&self) -> bool {
+        self.macros.is_empty()
+    }
+
+    /// Serialize the macros for storage
+    ///
+    /// The format is a version byte followed by `name length, name, step count, steps` for every
+    /// macro. A step is `action, intensity, duration in ms (u32 little endian), delay in ms (u32
+    /// little endian)`.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.push(FORMAT_VERSION);
+        for entry in &self.macros {
+            bytes.push(entry.name.len() as u8);
+            bytes.extend_from_slice(entry.name.as_bytes());
+            bytes.push(entry.steps.len() as u8);
+            for step in &entry.steps {
+                bytes.push(step.action as u8);
+                bytes.push(step.intensity);
+                bytes.extend_from_slice(&(step.duration.as_millis() as u32).to_le_bytes());
+                bytes.extend_from_slice(&(step.delay.as_millis() as u32).to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Read macros written by [`MacroBook::encode`]
+    pub fn decode(bytes: &[u8]) -> Result<Self, MacroBookError> {
+        let Some((&FORMAT_VERSION, mut bytes)) = bytes.split_first() else {
+            return Err(MacroBookError::Corrupted);
+        };
+        let mut book = Self::new();
+        while let Some((&length, rest)) = bytes.split_first() {
+            let Some((name, rest)) = rest.split_at_checked(length as usize) else {
+                return Err(MacroBookError::Corrupted);
+            };
+            let name = core::str::from_utf8(name).map_err(|_| MacroBookError::Corrupted)?;
+            let Some((&count, mut rest)) = rest.split_first() else {
+                return Err(MacroBookError::Corrupted);
+            };
+            if !is_valid_name(name) || !(1..=MAX_STEPS).contains(&(count as usize)) {
+                return Err(MacroBookError::Corrupted);
+            }
+            let mut steps = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let Some((step, remaining)) = rest.split_first_chunk::<ENCODED_STEP_LENGTH>()
+                else {
+                    return Err(MacroBookError::Corrupted);
+                };
+                steps.push(decode_step(step).ok_or(MacroBookError::Corrupted)?);
+                rest = remaining;
+            }
+            book.insert(Macro {
+                name: name.into(),
+                steps,
+            })?;
+            bytes = rest;
+        }
+        Ok(book)
+    }
+}
+
+/// Read a stored step, `None` if it is out of range
+fn decode_step(bytes: &[u8; ENCODED_STEP_LENGTH]) -> Option<Step> {
+    let [action, intensity, d0, d1, d2, d3, p0, p1, p2, p3] = *bytes;
+    let step = Step {
+        action: Action::try_from(action).ok()?,
+        intensity,
+        duration: Duration::from_millis(u32::from_le_bytes([d0, d1, d2, d3]) as u64),
+        delay: Duration::from_millis(u32::from_le_bytes([p0, p1, p2, p3]) as u64),
+    };
+    let valid = intensity <= 99
+        && !step.duration.is_zero()
+        && step.duration <= MAX_TRANSMIT_DURATION
+        && step.delay <= MAX_STEP_DELAY;
+    valid.then_some(step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    fn pattern() -> Macro {
+        let step = |action, intensity, duration, delay| Step {
+            action,
+            intensity,
+            duration: Duration::from_millis(duration),
+            delay: Duration::from_millis(delay),
+        };
+        Macro {
+            name: "tease".into(),
+            steps: vec![
+                step(Action::Vibrate, 30, 500, 1000),
+                step(Action::Vibrate, 60, 500, 0),
+                step(Action::Beep, 0, 200, 0),
+            ],
+        }
+    }
+
+    /// Test describing and timing a sequence
+    #[test]
+    fn describes_sequences() {
+        let pattern = pattern();
+        assert_eq!(
+            pattern.to_string(),
+            "vibrate 30 500ms pause 1000ms vibrate 60 500ms beep 200ms"
+        );
+        assert_eq!(total_duration(&pattern.steps), Duration::from_millis(2200));
+        assert_eq!(total_duration(&[]), Duration::ZERO);
+    }
+
+    /// Test that the stored format round trips and rejects garbage
+    #[test]
+    fn encodes_macros() {
+        let mut book = MacroBook::new();
+        assert_eq!(MacroBook::decode(&book.encode()), Ok(book.clone()));
+        book.insert(pattern()).unwrap();
+        book.insert(Macro {
+            name: "short".into(),
+            ..pattern()
+        })
+        .unwrap();
+        let bytes = book.encode();
+        assert_eq!(MacroBook::decode(&bytes), Ok(book.clone()));
+
+        assert_eq!(MacroBook::decode(&[]), Err(MacroBookError::Corrupted));
+        assert_eq!(
+            MacroBook::decode(&bytes[..bytes.len() - 1]),
+            Err(MacroBookError::Corrupted)
+        );
+        assert_eq!(
+            MacroBook::decode(&[1, 1, b'a', 0]),
+            Err(MacroBookError::Corrupted)
+        );
+
+        assert_eq!(book.remove("tease"), Some(pattern()));
+        for index in 0..MAX_MACROS - 1 {
+            book.insert(Macro {
+                name: alloc::format!("m{}", index),
+                ..pattern()
+            })
+            .unwrap();
+        }
+        assert_eq!(book.insert(pattern()), Err(MacroBookError::Full));
+    }
+}
@@ -0,0 +1,1811 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+use core::{
+    fmt::{self, Write},
+    time::Duration,
+};
+
+use crate::{
+    binary::{ErrorCode, Request, Response, StatusReport},
+    collar::{AddressBook, Collar},
+    command::{
+        self, CollarCommand, Command, LimitChanges, MacroCommand, Mode, Overrides, ParseError,
+        RandomCommand, HELP,
+    },
+    json::Object,
+    limits::{Limits, Target, ENCODED_LIMITS_LENGTH},
+    packet::{Action, Channel, Packet},
+    protocol::ProtocolKind,
+    queue::{Finished, Queue, Repeat, SendError},
+    random::{Poll, RandomSession},
+    sequence::{self, Macro, MacroBook},
+    status::{RandomState, Status},
+    storage::Storage,
+    transmitter::Transmitter,
+};
+
+/// How long limits can be changed after unlocking them
+pub const UNLOCK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Add a line to the reply of a command
+macro_rules! reply {
+    ($reply:expr, $($arg:tt)*) => {
+        writeln!($reply, $($arg)*).expect("writing to a string cannot fail")
+    };
+}
+
+/// Reason a command failed
+struct Failure {
+    /// Stable identifier of the error for machine readable output
+    code: &'static str,
+    /// Human readable description of the error
+    message: String,
+}
+
+impl Failure {
+    /// Error code of the binary protocol that describes the failure best
+    fn error_code(&self) -> ErrorCode {
+        match self.code {
+            "encode_failed" => ErrorCode::Encode,
+            "intensity_limit" | "duration_limit" | "cooldown" => ErrorCode::Limit,
+            _ => ErrorCode::InvalidValue,
+        }
+    }
+}
+
+impl From<ParseError> for Failure {
+    fn from(error: ParseError) -> Self {
+        Self {
+            code: error.code(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<SendError> for Failure {
+    fn from(error: SendError) -> Self {
+        Self {
+            code: error.code(),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Asynchronous events that are reported outside of command replies
+pub enum Event<'a> {
+    /// The queue finished a job
+    Finished(Finished),
+    /// The emergency stop button stopped the queue
+    StopButton,
+    /// A frame of another remote was received while sniffing
+    Received(&'a Packet),
+    /// The identity of a remote was learned
+    Learned(&'a Packet),
+    /// No remote was found while learning
+    LearnExpired,
+    /// The random session queued a packet
+    RandomFired(&'a Packet),
+    /// The random session could not queue a packet
+    RandomSkipped(&'a SendError),
+    /// The random session is over
+    RandomFinished { fired: u32 },
+}
+
+/// Interactive shell controlling the transmit queue
+///
+/// Holds the configuration, which is loaded from and saved to the storage, and executes text
+/// commands as well as requests of the binary protocol. Output is written to the given writers,
+/// so every transport gets its own replies. Times are passed in as the time since boot.
+pub struct Shell<T: Transmitter, S: Storage> {
+    /// Name of the saved collar the configuration was taken from
+    pub collar: Option<String>,
+    /// Shocker ID
+    pub id: u16,
+    /// Channel to transmit on
+    pub channel: Channel,
+    /// Intensity of the command
+    pub intensity: u8,
+    /// Action to perform
+    pub action: Action,
+    /// Protocol of the collar
+    pub protocol: ProtocolKind,
+    /// GPIO the receiver is connected to
+    pub rx_pin: u8,
+    /// Whether received frames should be printed
+    pub sniffing: bool,
+    /// Deadline until which the next received frame is learned
+    pub learn_deadline: Option<Duration>,
+    /// GPIO of the emergency stop button
+    pub stop_pin: Option<u8>,
+    /// Passkey BLE clients pair with, the services are only advertised while it is set
+    pub passkey: Option<u32>,
+    /// Output format of the shell
+    pub mode: Mode,
+    /// Firmware version reported by the status
+    pub version: &'static str,
+
+    /// Saved collars
+    collars: AddressBook,
+    /// Saved sequences
+    macros: MacroBook,
+    /// Random session in progress
+    random: Option<RandomSession>,
+    /// Time until which the limits can be changed
+    unlocked_until: Option<Duration>,
+    /// Queue of frames waiting to be transmitted
+    queue: Queue<T>,
+    /// Storage of the configuration
+    storage: S,
+}
+
+impl<T: Transmitter, S: Storage> Shell<T, S> {
+    /// Create a new shell, loading the configuration from the storage
+    pub fn new(transmitter: T, storage: S) -> Self {
+        // grab values from storage
+        let id = storage.get_u16("id").unwrap_or(0);
+        let intensity = storage.get_u8("intensity").unwrap_or(1);
+        let action = storage.get_u8("action").unwrap_or(1);
+        let action = Action::try_from(action).unwrap_or_else(|error| {
+            log::warn!(
+                "Stored action is corrupted ({}), falling back to shock",
+                error
+            );
+            Action::Shock
+        });
+        let channel = storage.get_u8("channel").unwrap_or(0);
+        let channel = Channel::try_from(channel).unwrap_or_else(|error| {
+            log::warn!("Stored channel is corrupted ({}), falling back to 0", error);
+            Channel::Zero
+        });
+        let protocol = storage.get_u8("protocol").unwrap_or(0);
+        let protocol = ProtocolKind::try_from(protocol).unwrap_or_else(|error| {
+            log::warn!(
+                "Stored protocol is corrupted ({}), falling back to caixianlin",
+                error
+            );
+            ProtocolKind::CaiXianLin
+        });
+        let rx_pin = storage
+            .get_u8("rx_pin")
+            .filter(|pin| command::is_usable_gpio(*pin))
+            .unwrap_or(1);
+        let stop_pin = storage
+            .get_u8("stop_pin")
+            .filter(|pin| command::is_usable_gpio(*pin));
+        let passkey = storage
+            .get_blob("passkey")
+            .and_then(|bytes| <[u8; 4]>::try_from(bytes.as_slice()).ok())
+            .map(u32::from_le_bytes);
+        let mode = match storage.get_u8("mode") {
+            Some(1) => Mode::Json,
+            _ => Mode::Text,
+        };
+        let collars = match storage.get_blob("collars") {
+            Some(bytes) => AddressBook::decode(&bytes).unwrap_or_else(|error| {
+                log::warn!("{}, starting with an empty one", error);
+                AddressBook::new()
+            }),
+            None => AddressBook::new(),
+        };
+        let macros = match storage.get_blob("macros") {
+            Some(bytes) => MacroBook::decode(&bytes).unwrap_or_else(|error| {
+                log::warn!("{}, starting without macros", error);
+                MacroBook::new()
+            }),
+            None => MacroBook::new(),
+        };
+        let collar = storage
+            .get_blob("collar")
+            .and_then(|name| String::from_utf8(name).ok())
+            .filter(|name| collars.get(name).is_some());
+        let limits = storage.get_blob("limits").map(|bytes| {
+            <[u8; ENCODED_LIMITS_LENGTH]>::try_from(bytes.as_slice())
+                .ok()
+                .and_then(|bytes| Limits::decode(&bytes))
+                .unwrap_or_else(|| {
+                    // falling back to no limits would be unsafe
+                    log::warn!("Stored limits are corrupted, blocking shock and vibrate");
+                    Limits {
+                        max_shock: 0,
+                        max_vibrate: 0,
+                        ..Limits::NONE
+                    }
+                })
+        });
+
+        let mut shell = Self {
+            collar,
+            id,
+            channel,
+            intensity,
+            action,
+            protocol,
+            rx_pin,
+            sniffing: false,
+            learn_deadline: None,
+            stop_pin,
+            passkey,
+            mode,
+            version: env!("CARGO_PKG_VERSION"),
+            collars,
+            macros,
+            random: None,
+            unlocked_until: None,
+            queue: Queue::new(transmitter),
+            storage,
+        };
+        shell.queue.set_limits(limits.unwrap_or(Limits::NONE));
+        shell.update_collar_settings();
+        shell
+    }
+
+    /// Queue of frames waiting to be transmitted
+    pub fn queue(&self) -> &Queue<T> {
+        &self.queue
+    }
+
+    /// Mutable access to the queue of frames waiting to be transmitted
+    pub fn queue_mut(&mut self) -> &mut Queue<T> {
+        &mut self.queue
+    }
+
+    /// Saved collars
+    pub fn collars(&self) -> &AddressBook {
+        &self.collars
+    }
+
+    /// Storage of the configuration
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Snapshot of the configuration and the activity of the queue
+    pub fn status(&self, now: Duration) -> Status {
+        Status {
+            version: self.version,
+            uptime: now,
+            collar: self.collar.clone(),
+            id: self.id,
+            channel: self.channel,
+            protocol: self.protocol,
+            action: self.action,
+            intensity: self.intensity,
+            rx_pin: self.rx_pin,
+            sniffing: self.sniffing,
+            stop_pin: self.stop_pin,
+            mode: self.mode,
+            queue_depth: self.queue.len(),
+            transmitting: self.queue.transmitter().is_busy(),
+            limits: self.queue.limits(),
+            random: self.random.as_ref().map(|session| RandomState {
+                seed: session.seed(),
+                fired: session.fired(),
+                remaining: session.remaining(now),
+            }),
+        }
+    }
+
+    /// Whether the receiver is needed
+    pub fn is_listening(&self) -> bool {
+        self.sniffing || self.learn_deadline.is_some()
+    }
+
+    /// Save the configuration to storage
+    pub fn store(&mut self) {
+        self.storage.set_u16("id", self.id);
+        self.storage.set_u8("intensity", self.intensity);
+        self.storage.set_u8("action", self.action as u8);
+        self.storage.set_u8("channel", self.channel as u8);
+        self.storage.set_u8("protocol", self.protocol as u8);
+        self.storage.set_u8("rx_pin", self.rx_pin);
+        self.storage.set_u8("mode", self.mode as u8);
+        match self.stop_pin {
+            Some(stop_pin) => self.storage.set_u8("stop_pin", stop_pin),
+            None => self.storage.remove("stop_pin"),
+        }
+        match self.passkey {
+            Some(passkey) => self.storage.set_blob("passkey", &passkey.to_le_bytes()),
+            None => self.storage.remove("passkey"),
+        }
+        match &self.collar {
+            Some(collar) => self.storage.set_blob("collar", collar.as_bytes()),
+            None => self.storage.remove("collar"),
+        }
+    }
+
+    /// Save the address book to storage and pass the settings of its collars on to the queue
+    fn store_collars(&mut self) {
+        self.storage.set_blob("collars", &self.collars.encode());
+        self.update_collar_settings();
+    }
+
+    /// Pass the limits and warnings of the saved collars on to the queue
+    fn update_collar_settings(&mut self) {
+        let limits = self
+            .collars
+            .iter()
+            .map(|collar| (collar.target(), collar.limits));
+        self.queue.set_collar_limits(limits);
+        let warnings = self
+            .collars
+            .iter()
+            .filter_map(|collar| Some((collar.target(), collar.warning?)));
+        self.queue.set_warnings(warnings);
+    }
+
+    /// Fail unless the limits were unlocked recently
+    fn check_unlocked(&self, now: Duration) -> Result<(), Failure> {
+        if self.unlocked_until.is_some_and(|until| now < until) {
+            return Ok(());
+        }
+        Err(Failure {
+            code: "locked",
+            message: "Limits are locked, run unlock first".into(),
+        })
+    }
+
+    /// Tick the queue, cancel a learn whose deadline has passed and run the random session,
+    /// reporting what happened
+    pub fn tick(&mut self, now: Duration, output: &mut impl Write) -> fmt::Result {
+        if let Some(finished) = self.queue.tick(now) {
+            self.notify(Event::Finished(finished), output)?;
+        }
+        if self.learn_deadline.is_some_and(|deadline| now >= deadline) {
+            self.learn_deadline = None;
+            self.notify(Event::LearnExpired, output)?;
+        }
+        let Some(session) = &mut self.random else {
+            return Ok(());
+        };
+        match session.poll(now) {
+            Poll::Idle => {}
+            // the limits may have changed since the session started, the queue checks them again
+            Poll::Fire(packet, repeat) => {
+                match self.queue.send(session.target().protocol, &packet, repeat) {
+                    Ok(()) => self.notify(Event::RandomFired(&packet), output)?,
+                    Err(error) => self.notify(Event::RandomSkipped(&error), output)?,
+                }
+            }
+            Poll::Finished => {
+                let fired = session.fired();
+                self.random = None;
+                self.notify(Event::RandomFinished { fired }, output)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop everything because the emergency stop button was pressed
+    pub fn stop_button(&mut self, output: &mut impl Write) -> fmt::Result {
+        self.queue.stop();
+        self.random = None;
+        self.notify(Event::StopButton, output)
+    }
+
+    /// Handle a packet received from another remote
+    ///
+    /// Prints it while sniffing and takes over its identity if a learn is in progress.
+    pub fn receive(&mut self, packet: &Packet, output: &mut impl Write) -> fmt::Result {
+        if self.sniffing {
+            self.notify(Event::Received(packet), output)?;
+        }
+        if self.learn_deadline.take().is_some() {
+            self.collar = None;
+            self.id = packet.id;
+            self.channel = packet.channel;
+            self.store();
+            self.notify(Event::Learned(packet), output)?;
+        }
+        Ok(())
+    }
+
+    /// Print an asynchronous event in the configured mode
+    pub fn notify(&self, event: Event, output: &mut impl Write) -> fmt::Result {
+        if self.mode == Mode::Text {
+            return match event {
+                // the queue is quiet in text mode
+                Event::Finished(_) => Ok(()),
+                Event::StopButton => {
+                    writeln!(output, "Emergency stop button pressed")?;
+                    writeln!(output, "Stopped, transmit queue is empty")
+                }
+                Event::Received(packet) => writeln!(
+                    output,
+                    "Received {:?} from remote {} on channel {} with intensity {}",
+                    packet.action, packet.id, packet.channel as u8, packet.intensity
+                ),
+                Event::Learned(packet) => writeln!(
+                    output,
+                    "Learned ID {} on channel {}",
+                    packet.id, packet.channel as u8
+                ),
+                Event::LearnExpired => {
+                    writeln!(output, "No remote found, ID and channel are unchanged")
+                }
+                Event::RandomFired(packet) => writeln!(
+                    output,
+                    "Random {:?} with intensity {}",
+                    packet.action, packet.intensity
+                ),
+                Event::RandomSkipped(error) => writeln!(output, "Random action skipped: {}", error),
+                Event::RandomFinished { fired } => {
+                    writeln!(output, "Random session finished after {} actions", fired)
+                }
+            };
+        }
+
+        match event {
+            Event::Finished(finished) => {
+                write_json(output, |object| {
+                    object
+                        .string("event", "transmission_finished")?
+                        .number("queue_depth", finished.remaining as i64)?;
+                    Ok(())
+                })?;
+                if finished.remaining == 0 {
+                    write_json(output, |object| {
+                        object.string("event", "queue_drained")?;
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            }
+            Event::StopButton => write_json(output, |object| {
+                object.string("event", "stop_button")?;
+                Ok(())
+            }),
+            Event::Received(packet) => write_packet(output, "received", packet),
+            Event::Learned(packet) => write_packet(output, "learned", packet),
+            Event::LearnExpired => write_json(output, |object| {
+                object.string("event", "learn_expired")?;
+                Ok(())
+            }),
+            Event::RandomFired(packet) => write_packet(output, "random_fired", packet),
+            Event::RandomSkipped(error) => write_json(output, |object| {
+                object
+                    .string("event", "random_skipped")?
+                    .string("code", error.code())?
+                    .string("message", &error.to_string())?;
+                Ok(())
+            }),
+            Event::RandomFinished { fired } => write_json(output, |object| {
+                object
+                    .string("event", "random_finished")?
+                    .number("fired", fired)?;
+                Ok(())
+            }),
+        }
+    }
+
+    /// Process a command line and write the reply in the configured mode
+    ///
+    /// In JSON mode every command is answered with exactly one object carrying the outcome, an
+    /// error code and the resulting state.
+    pub fn process_command(
+        &mut self,
+        line: &str,
+        now: Duration,
+        output: &mut impl Write,
+    ) -> fmt::Result {
+        let mut reply = String::new();
+        let result = match command::parse(line) {
+            Ok(command) => self.execute(command, now, &mut reply),
+            Err(ParseError::Empty) => return Ok(()),
+            Err(error @ ParseError::UnknownCommand(_)) if self.mode == Mode::Text => {
+                reply!(reply, "{}", error);
+                reply!(reply, "{}", HELP);
+                Ok(())
+            }
+            Err(error) => Err(error.into()),
+        };
+        self.write_reply(result, &reply, now, output)
+    }
+
+    /// Execute a command that was not typed, like a write to a BLE characteristic
+    ///
+    /// Returns the text reply, or the error code of the binary protocol closest to the failure,
+    /// so the caller answers its own client instead of writing to the serial port.
+    pub fn process_parsed(&mut self, command: Command, now: Duration) -> Result<String, ErrorCode> {
+        let mut reply = String::new();
+        self.execute(command, now, &mut reply)
+            .map_err(|failure| failure.error_code())?;
+        Ok(reply)
+    }
+
+    /// Write the outcome of a command in the configured mode
+    fn write_reply(
+        &self,
+        result: Result<(), Failure>,
+        reply: &str,
+        now: Duration,
+        output: &mut impl Write,
+    ) -> fmt::Result {
+        match self.mode {
+            Mode::Text => {
+                output.write_str(reply)?;
+                if let Err(failure) = result {
+                    writeln!(output, "{}", failure.message)?;
+                }
+                Ok(())
+            }
+            Mode::Json => write_json(output, |object| {
+                match &result {
+                    Ok(()) => object
+                        .bool("ok", true)?
+                        .null("code")?
+                        .string("message", reply.trim_end())?,
+                    Err(failure) => object
+                        .bool("ok", false)?
+                        .string("code", failure.code)?
+                        .string("message", &failure.message)?,
+                };
+                object.object("state", |object| self.status(now).write_fields(object))?;
+                Ok(())
+            }),
+        }
+    }
+
+    /// Execute a command, writing the human readable reply
+    fn execute(
+        &mut self,
+        command: Command,
+        now: Duration,
+        reply: &mut String,
+    ) -> Result<(), Failure> {
+        match command {
+            Command::Help => {
+                reply!(reply, "{}", HELP);
+            }
+
+            Command::Id(id) => {
+                self.collar = None;
+                self.id = id;
+                self.store();
+                reply!(reply, "Setting ID to {}", id);
+            }
+
+            Command::Channel(channel) => {
+                self.collar = None;
+                self.channel = channel;
+                self.store();
+                reply!(reply, "Setting channel to {}", channel as u8);
+            }
+
+            Command::Intensity(intensity) => {
+                self.intensity = intensity;
+                self.store();
+                reply!(reply, "Setting intensity to {}", intensity);
+            }
+
+            Command::Vibrate(intensity) => {
+                if let Some(intensity) = intensity {
+                    self.intensity = intensity;
+                }
+                self.action = Action::Vibrate;
+                self.store();
+                reply!(reply, "Setting action to vibrate {}", self.intensity);
+            }
+
+            Command::Shock(intensity) => {
+                if let Some(intensity) = intensity {
+                    self.intensity = intensity;
+                }
+                self.action = Action::Shock;
+                self.store();
+                reply!(reply, "Setting action to shock {}", self.intensity);
+            }
+
+            Command::Beep => {
+                self.action = Action::Beep;
+                self.store();
+                reply!(reply, "Setting action to beep");
+            }
+
+            Command::Light => {
+                self.action = Action::Light;
+                self.store();
+                reply!(reply, "Setting action to light");
+            }
+
+            Command::Protocol(None) => {
+                reply!(
+                    reply,
+                    "Protocol is {}, available protocols:",
+                    self.protocol.name()
+                );
+                for protocol in ProtocolKind::ALL {
+                    reply!(reply, "  {}", protocol.name());
+                }
+            }
+
+            Command::Protocol(Some(protocol)) => {
+                self.collar = None;
+                self.protocol = protocol;
+                self.store();
+                reply!(reply, "Setting protocol to {}", protocol.name());
+            }
+
+            Command::Sniff(sniffing) => {
+                self.sniffing = sniffing.unwrap_or(!self.sniffing);
+                if self.sniffing {
+                    reply!(
+                        reply,
+                        "Listening for {} frames on GPIO {}",
+                        self.protocol.name(),
+                        self.rx_pin
+                    );
+                } else {
+                    reply!(reply, "Stopped listening");
+                }
+            }
+
+            Command::RxPin(pin) if Some(pin) == self.stop_pin => {
+                return Err(Failure {
+                    code: "pin_in_use",
+                    message: format!("GPIO {} is used by the stop button", pin),
+                });
+            }
+
+            Command::RxPin(pin) => {
+                self.rx_pin = pin;
+                self.store();
+                reply!(reply, "Setting receiver GPIO to {}", pin);
+            }
+
+            Command::Learn(timeout) => {
+                self.learn_deadline = Some(now + Duration::from_secs(timeout as u64));
+                reply!(
+                    reply,
+                    "Press a button on the {} remote within {} seconds",
+                    self.protocol.name(),
+                    timeout
+                );
+            }
+
+            Command::Transmit(repeat) => {
+                self.transmit(&Overrides::default(), repeat, reply)?;
+            }
+
+            Command::Send(overrides, repeat) => {
+                self.transmit(&overrides, repeat, reply)?;
+            }
+
+            Command::Stop => {
+                self.queue.stop();
+                reply!(reply, "Stopped, transmit queue is empty");
+                if self.random.take().is_some() {
+                    reply!(reply, "Random session stopped");
+                }
+            }
+
+            // the state is part of every reply in JSON mode
+            Command::Status { .. } if self.mode == Mode::Json => {}
+
+            Command::Status { json: false } => {
+                reply!(reply, "{}", self.status(now));
+            }
+
+            Command::Status { json: true } => {
+                self.status(now)
+                    .write_json(reply)
+                    .expect("writing to a string cannot fail");
+                reply.push('\n');
+            }
+
+            Command::Mode(None) => {
+                reply!(reply, "Mode is {}", self.mode.name());
+            }
+
+            Command::Mode(Some(mode)) => {
+                self.mode = mode;
+                self.store();
+                reply!(reply, "Setting mode to {}", mode.name());
+            }
+
+            Command::StopPin(Some(pin)) if pin == self.rx_pin => {
+                return Err(Failure {
+                    code: "pin_in_use",
+                    message: format!("GPIO {} is used by the receiver", pin),
+                });
+            }
+
+            Command::StopPin(pin) => {
+                self.stop_pin = pin;
+                self.store();
+                match pin {
+                    Some(pin) => reply!(reply, "Setting stop button GPIO to {}", pin),
+                    None => reply!(reply, "Disabling stop button"),
+                }
+            }
+
+            Command::Bluetooth(None) => match self.passkey {
+                Some(passkey) => reply!(reply, "Advertising over BLE with passkey {:06}", passkey),
+                None => reply!(reply, "BLE is off"),
+            },
+
+            Command::Bluetooth(Some(passkey)) => {
+                self.passkey = passkey;
+                self.store();
+                match passkey {
+                    Some(passkey) => {
+                        reply!(reply, "Advertising over BLE with passkey {:06}", passkey)
+                    }
+                    None => reply!(reply, "BLE is off"),
+                }
+            }
+
+            Command::Collar(command) => self.execute_collar(command, now, reply)?,
+
+            Command::Limits { collar, changes } => {
+                self.execute_limits(collar, changes, now, reply)?
+            }
+
+            Command::Ramp { ramp, collar } => {
+                let target = self.target(collar.as_deref())?;
+                let steps = ramp.steps();
+                self.queue.send_sequence(target, &steps)?;
+                reply!(reply, "Ramping {} in {} steps", ramp, steps.len());
+            }
+
+            Command::Random(command) => self.execute_random(command, now, reply)?,
+
+            Command::Macro(command) => self.execute_macro(command, reply)?,
+
+            Command::Unlock => {
+                self.unlocked_until = Some(now + UNLOCK_TIMEOUT);
+                reply!(
+                    reply,
+                    "Limits can be changed for the next {} seconds",
+                    UNLOCK_TIMEOUT.as_secs()
+                );
+            }
+
+            Command::Lock => {
+                self.unlocked_until = None;
+                reply!(reply, "Limits are locked");
+            }
+        }
+        Ok(())
+    }
+
+    /// Print or change the limits of a collar or the global ones
+    fn execute_limits(
+        &mut self,
+        collar: Option<String>,
+        changes: LimitChanges,
+        now: Duration,
+        reply: &mut String,
+    ) -> Result<(), Failure> {
+        let Some(name) = collar else {
+            if !changes.is_empty() {
+                self.check_unlocked(now)?;
+                let limits = changes.apply(&self.queue.limits());
+                self.queue.set_limits(limits);
+                self.storage.set_blob("limits", &limits.encode());
+                reply!(reply, "Setting limits to {}", limits);
+                return Ok(());
+            }
+            reply!(reply, "Limits: {}", self.queue.limits());
+            for collar in self.collars.iter() {
+                if collar.limits != Limits::NONE {
+                    reply!(reply, "Limits of {}: {}", collar.name, collar.limits);
+                }
+            }
+            return Ok(());
+        };
+
+        let collar = self
+            .collars
+            .get(&name)
+            .ok_or_else(|| unknown_collar(&name))?;
+        if changes.is_empty() {
+            reply!(reply, "Limits of {}: {}", collar.name, collar.limits);
+            return Ok(());
+        }
+        self.check_unlocked(now)?;
+        let collar = Collar {
+            limits: changes.apply(&collar.limits),
+            ..collar.clone()
+        };
+        reply!(
+            reply,
+            "Setting limits of {} to {}",
+            collar.name,
+            collar.limits
+        );
+        self.collars
+            .insert(collar)
+            .expect("replacing a collar always fits");
+        self.store_collars();
+        Ok(())
+    }
+
+    /// Execute an address book command
+    fn execute_collar(
+        &mut self,
+        command: CollarCommand,
+        now: Duration,
+        reply: &mut String,
+    ) -> Result<(), Failure> {
+        match command {
+            CollarCommand::Add {
+                name,
+                id,
+                channel,
+                protocol,
+                max_intensity,
+            } => {
+                // replacing a collar keeps its settings, limits and warning
+                let existing = self.collars.get(&name);
+                let mut limits = existing.map_or(Limits::NONE, |collar| collar.limits);
+                let warning = existing.and_then(|collar| collar.warning);
+                if let Some(max_intensity) = max_intensity {
+                    self.check_unlocked(now)?;
+                    limits.max_shock = max_intensity;
+                    limits.max_vibrate = max_intensity;
+                }
+                let collar = Collar {
+                    name,
+                    id: id.or(existing.map(|collar| collar.id)).unwrap_or(self.id),
+                    channel: channel
+                        .or(existing.map(|collar| collar.channel))
+                        .unwrap_or(self.channel),
+                    protocol: protocol
+                        .or(existing.map(|collar| collar.protocol))
+                        .unwrap_or(self.protocol),
+                    limits,
+                    warning,
+                };
+                // moving the limits or warning to another collar is as sensitive as dropping them
+                let moved = existing.is_some_and(|existing| {
+                    existing.target() != collar.target()
+                        && (existing.limits != Limits::NONE || existing.warning.is_some())
+                });
+                if moved {
+                    self.check_unlocked(now)?;
+                }
+                reply!(reply, "Saved collar {}: {}", collar.name, collar);
+                self.collars.insert(collar).map_err(|error| Failure {
+                    code: "address_book_full",
+                    message: error.to_string(),
+                })?;
+                self.store_collars();
+            }
+
+            CollarCommand::Remove(name) => {
+                let collar = self
+                    .collars
+                    .get(&name)
+                    .ok_or_else(|| unknown_collar(&name))?;
+                // dropping a collar drops its limits and warning
+                if collar.limits != Limits::NONE || collar.warning.is_some() {
+                    self.check_unlocked(now)?;
+                }
+                self.collars.remove(&name);
+                self.store_collars();
+                if self.collar.as_ref() == Some(&name) {
+                    self.collar = None;
+                    self.store();
+                }
+                reply!(reply, "Removed collar {}", name);
+            }
+
+            CollarCommand::List if self.collars.is_empty() => {
+                reply!(reply, "No collars saved, add one with collar add <name>");
+            }
+
+            CollarCommand::List => {
+                reply!(reply, "Saved collars:");
+                for collar in self.collars.iter() {
+                    let active = self.collar.as_ref() == Some(&collar.name);
+                    reply!(
+                        reply,
+                        "{} {}: {}",
+                        if active { "*" } else { " " },
+                        collar.name,
+                        collar
+                    );
+                }
+            }
+
+            CollarCommand::Use(name) => {
+                let collar = self
+                    .collars
+                    .get(&name)
+                    .ok_or_else(|| unknown_collar(&name))?;
+                self.id = collar.id;
+                self.channel = collar.channel;
+                self.protocol = collar.protocol;
+                reply!(reply, "Using collar {}: {}", collar.name, collar);
+                self.collar = Some(name);
+                self.store();
+            }
+
+            CollarCommand::Warn { name, warning } => {
+                let collar = self
+                    .collars
+                    .get(&name)
+                    .ok_or_else(|| unknown_collar(&name))?;
+                // the warning may be mandatory, so dropping it is as sensitive as the limits
+                if warning.is_none() && collar.warning.is_some() {
+                    self.check_unlocked(now)?;
+                }
+                let collar = Collar {
+                    warning,
+                    ..collar.clone()
+                };
+                match &collar.warning {
+                    Some(warning) => reply!(reply, "Warning of {}: {}", collar.name, warning),
+                    None => reply!(reply, "Shocking {} without warning", collar.name),
+                }
+                self.collars
+                    .insert(collar)
+                    .expect("replacing a collar always fits");
+                self.store_collars();
+            }
+        }
+        Ok(())
+    }
+
+    /// Execute a subcommand of the random session
+    fn execute_random(
+        &mut self,
+        command: RandomCommand,
+        now: Duration,
+        reply: &mut String,
+    ) -> Result<(), Failure> {
+        match command {
+            RandomCommand::Start { .. } if self.random.is_some() => {
+                return Err(Failure {
+                    code: "random_running",
+                    message: "A random session is running, stop it first".into(),
+                });
+            }
+
+            RandomCommand::Start { settings, collar } => {
+                let target = self.target(collar.as_deref())?;
+                settings
+                    .check(&self.queue.limits_for(&target))
+                    .map_err(SendError::from)?;
+                // the time since boot is as good a seed as any
+                let seed = settings.seed.unwrap_or(now.as_micros() as u32);
+                let session = RandomSession::new(settings, target, seed, now);
+                reply!(
+                    reply,
+                    "Starting random {} for {}s with seed {}",
+                    session.settings(),
+                    session.remaining(now).as_secs(),
+                    seed
+                );
+                self.random = Some(session);
+            }
+
+            RandomCommand::Stop => match self.random.take() {
+                Some(session) => reply!(
+                    reply,
+                    "Random session stopped after {} actions",
+                    session.fired()
+                ),
+                None => reply!(reply, "No random session is running"),
+            },
+
+            RandomCommand::Show => match &self.random {
+                Some(session) => reply!(
+                    reply,
+                    "Random {}, {} fired, {}s left, seed {}",
+                    session.settings(),
+                    session.fired(),
+                    session.remaining(now).as_secs(),
+                    session.seed()
+                ),
+                None => reply!(reply, "No random session is running"),
+            },
+        }
+        Ok(())
+    }
+
+    /// Execute a subcommand of the saved sequences
+    fn execute_macro(&mut self, command: MacroCommand, reply: &mut String) -> Result<(), Failure> {
+        match command {
+            MacroCommand::Define { name, steps } => {
+                let entry = Macro { name, steps };
+                reply!(reply, "Saved macro {}: {}", entry.name, entry);
+                self.macros.insert(entry).map_err(|error| Failure {
+                    code: "macros_full",
+                    message: error.to_string(),
+                })?;
+                self.storage.set_blob("macros", &self.macros.encode());
+            }
+
+            MacroCommand::Run { name, collar } => {
+                let target = self.target(collar.as_deref())?;
+                let entry = self.macros.get(&name).ok_or_else(|| unknown_macro(&name))?;
+                self.queue.send_sequence(target, &entry.steps)?;
+                reply!(
+                    reply,
+                    "Running macro {} with {} steps in {}ms",
+                    entry.name,
+                    entry.steps.len(),
+                    sequence::total_duration(&entry.steps).as_millis()
+                );
+            }
+
+            MacroCommand::List if self.macros.is_empty() => {
+                reply!(
+                    reply,
+                    "No macros saved, add one with macro define <name> <steps>"
+                );
+            }
+
+            MacroCommand::List => {
+                reply!(reply, "Saved macros:");
+                for entry in self.macros.iter() {
+                    reply!(reply, "  {}: {}", entry.name, entry);
+                }
+            }
+
+            MacroCommand::Delete(name) => {
+                self.macros
+                    .remove(&name)
+                    .ok_or_else(|| unknown_macro(&name))?;
+                self.storage.set_blob("macros", &self.macros.encode());
+                reply!(reply, "Deleted macro {}", name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Collar given by name, else the active collar, else the configured identity
+    fn target(&self, collar: Option<&str>) -> Result<Target, Failure> {
+        let collar = match collar {
+            Some(name) => Some(self.collars.get(name).ok_or_else(|| unknown_collar(name))?),
+            None => self.collar.as_ref().and_then(|name| self.collars.get(name)),
+        };
+        Ok(collar.map_or(
+            Target {
+                protocol: self.protocol,
+                id: self.id,
+                channel: self.channel,
+            },
+            Collar::target,
+        ))
+    }
+
+    /// Queue the configured packet with the given settings overridden
+    fn transmit(
+        &mut self,
+        overrides: &Overrides,
+        repeat: Repeat,
+        reply: &mut String,
+    ) -> Result<(), Failure> {
+        // a collar given by name replaces the configured one
+        let collar = match &overrides.collar {
+            Some(name) => Some(self.collars.get(name).ok_or_else(|| unknown_collar(name))?),
+            None => self.collar.as_ref().and_then(|name| self.collars.get(name)),
+        };
+
+        // build packet
+        let packet = overrides.apply(&Packet {
+            id: collar.map_or(self.id, |collar| collar.id),
+            channel: collar.map_or(self.channel, |collar| collar.channel),
+            action: self.action,
+            intensity: self.intensity,
+        });
+        let kind = overrides
+            .protocol
+            .or(collar.map(|collar| collar.protocol))
+            .unwrap_or(self.protocol);
+
+        // send packet, the queue checks it against the limits
+        self.queue.send(kind, &packet, repeat)?;
+        let protocol = kind.protocol();
+        let frame = protocol
+            .encode(&packet)
+            .expect("the queue encoded the packet already");
+        reply!(
+            reply,
+            "Sending {:?} to shocker {} on channel {:?} with intensity {}",
+            packet.action,
+            packet.id,
+            packet.channel,
+            packet.intensity
+        );
+        let warning = self
+            .queue
+            .warning_for(&Target::new(kind, &packet))
+            .filter(|_| packet.action == Action::Shock);
+        if let Some(warning) = warning {
+            reply!(reply, "Warning first: {}", warning);
+        }
+        match repeat {
+            Repeat::Count(amount) => reply!(reply, "Repeating {} times", amount),
+            Repeat::For(duration) => reply!(
+                reply,
+                "Repeating for {}ms, {}us per frame",
+                duration.as_millis(),
+                protocol.timings().airtime(&frame).as_micros()
+            ),
+        }
+        Ok(())
+    }
+
+    /// Execute a request of the binary protocol
+    pub fn process_request(&mut self, request: Request, now: Duration) -> Response {
+        match request {
+            Request::SetConfig { protocol, packet } => {
+                self.collar = None;
+                self.protocol = protocol;
+                self.id = packet.id;
+                self.channel = packet.channel;
+                self.action = packet.action;
+                self.intensity = packet.intensity;
+                self.store();
+                Response::Ack
+            }
+
+            Request::Transmit {
+                protocol,
+                packet,
+                repeat,
+            } => match self.queue.send(protocol, &packet, repeat) {
+                Ok(()) => Response::Ack,
+                Err(SendError::Encode(_)) => Response::Error(ErrorCode::Encode),
+                Err(SendError::Limit(_)) => Response::Error(ErrorCode::Limit),
+            },
+
+            Request::Stop => {
+                self.queue.stop();
+                self.random = None;
+                Response::Ack
+            }
+
+            Request::Status => Response::Status(StatusReport::from(&self.status(now))),
+        }
+    }
+}
+
+/// Failure for a collar that is not in the address book
+fn unknown_collar(name: &str) -> Failure {
+    Failure {
+        code: "unknown_collar",
+        message: format!("Unknown collar {}, see collar list", name),
+    }
+}
+
+/// Failure for a macro that is not saved
+fn unknown_macro(name: &str) -> Failure {
+    Failure {
+        code: "unknown_macro",
+        message: format!("Unknown macro {}, see macro list", name),
+    }
+}
+
+/// Write a JSON object on a single line
+fn write_json<W: Write>(
+    output: &mut W,
+    write: impl FnOnce(&mut Object<W>) -> fmt::Result,
+) -> fmt::Result {
+    let mut object = Object::new(output)?;
+    write(&mut object)?;
+    object.finish()?;
+    output.write_char('\n')
+}
+
+/// Write an event about a packet as JSON
+fn write_packet(output: &mut impl Write, event: &str, packet: &Packet) -> fmt::Result {
+    write_json(output, |object| {
+        object
+            .string("event", event)?
+            .number("id", packet.id)?
+            .number("channel", packet.channel as u8)?
+            .string("action", packet.action.name())?
+            .number("intensity", packet.intensity)?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{storage::MemoryStorage, transmitter::RecordingTransmitter};
+    use alloc::vec::Vec;
+
+    type TestShell = Shell<RecordingTransmitter, MemoryStorage>;
+
+    /// Run a command and return its output
+    fn run(shell: &mut TestShell, line: &str) -> String {
+        let mut output = String::new();
+        shell
+            .process_command(line, Duration::from_secs(1), &mut output)
+            .unwrap();
+        output
+    }
+
+    /// Test that settings are applied and stored
+    #[test]
+    fn stores_configuration() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        assert_eq!(run(&mut shell, "id 1234"), "Setting ID to 1234\n");
+        assert_eq!(run(&mut shell, "shock 30"), "Setting action to shock 30\n");
+        assert_eq!(
+            run(&mut shell, "stoppin 9"),
+            "Setting stop button GPIO to 9\n"
+        );
+
+        // a new shell on the same storage picks everything up
+        let storage = shell.storage().clone();
+        let shell = Shell::new(RecordingTransmitter::new(), storage);
+        assert_eq!(shell.id, 1234);
+        assert_eq!(shell.action, Action::Shock);
+        assert_eq!(shell.intensity, 30);
+        assert_eq!(shell.stop_pin, Some(9));
+        assert_eq!(shell.passkey, None);
+    }
+
+    /// Test that BLE stays off until it gets a passkey
+    #[test]
+    fn stores_passkey() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        assert_eq!(run(&mut shell, "ble"), "BLE is off\n");
+        assert_eq!(
+            run(&mut shell, "ble on 4321"),
+            "Advertising over BLE with passkey 004321\n"
+        );
+
+        let mut shell = Shell::new(RecordingTransmitter::new(), shell.storage().clone());
+        assert_eq!(shell.passkey, Some(4321));
+        assert_eq!(run(&mut shell, "ble off"), "BLE is off\n");
+        let shell = Shell::new(RecordingTransmitter::new(), shell.storage().clone());
+        assert_eq!(shell.passkey, None);
+    }
+
+    /// Test that the receiver and the stop button cannot share a GPIO
+    #[test]
+    fn rejects_pins_in_use() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        assert_eq!(
+            run(&mut shell, "stoppin 1"),
+            "GPIO 1 is used by the receiver\n"
+        );
+        assert_eq!(shell.stop_pin, None);
+        run(&mut shell, "stoppin 9");
+        assert_eq!(
+            run(&mut shell, "rxpin 9"),
+            "GPIO 9 is used by the stop button\n"
+        );
+        assert_eq!(shell.rx_pin, 1);
+        assert_eq!(
+            run(&mut shell, "rxpin 13"),
+            "GPIO 13 is reserved for the flash or the console\n"
+        );
+    }
+
+    /// Test that send transmits without touching the configuration
+    #[test]
+    fn sends_without_storing() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        let output = run(&mut shell, "send id=4321 ch=2 vibrate 40 x2");
+        assert_eq!(
+            output,
+            "Sending Vibrate to shocker 4321 on channel Two with intensity 40\nRepeating 2 times\n"
+        );
+        assert_eq!(shell.queue().len(), 1);
+        assert_eq!(shell.id, 0);
+        assert_eq!(shell.storage().get_u16("id"), None);
+    }
+
+    /// Test that errors are printed and the help follows unknown commands
+    #[test]
+    fn prints_errors() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        assert_eq!(
+            run(&mut shell, "shock abc"),
+            "Intensity must be a number, got abc\n"
+        );
+        assert!(run(&mut shell, "meow").starts_with("Unknown command meow\nAvailable commands:"));
+        assert_eq!(run(&mut shell, ""), "");
+    }
+
+    /// Test that JSON mode answers every command with exactly one object
+    #[test]
+    fn replies_with_json() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        let output = run(&mut shell, "mode json");
+        assert!(output.starts_with(
+            r#"{"ok":true,"code":null,"message":"Setting mode to json","state":{"version":"#
+        ));
+        assert!(output.contains("\"transmitting\":false,\"limits\":{"));
+
+        for line in ["help", "status", "status --json", "intensity 100", "meow"] {
+            let output = run(&mut shell, line);
+            assert_eq!(output.lines().count(), 1, "{}", output);
+        }
+        assert!(run(&mut shell, "intensity 100")
+            .starts_with(r#"{"ok":false,"code":"out_of_range","message":"Intensity must"#));
+    }
+
+    /// Test that finished jobs are reported in JSON mode only
+    #[test]
+    fn reports_events() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        run(&mut shell, "transmit 1");
+
+        let mut output = String::new();
+        shell.tick(Duration::ZERO, &mut output).unwrap();
+        shell.queue_mut().transmitter_mut().finish();
+        shell.tick(Duration::ZERO, &mut output).unwrap();
+        assert_eq!(output, "");
+
+        run(&mut shell, "mode json");
+        run(&mut shell, "transmit 1");
+        shell.tick(Duration::ZERO, &mut output).unwrap();
+        shell.queue_mut().transmitter_mut().finish();
+        shell.tick(Duration::ZERO, &mut output).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                r#"{"event":"transmission_finished","queue_depth":0}"#,
+                r#"{"event":"queue_drained"}"#
+            ]
+        );
+    }
+
+    /// Test learning the identity of a remote
+    #[test]
+    fn learns_remotes() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        run(&mut shell, "learn 10");
+        assert!(shell.is_listening());
+
+        let packet = Packet {
+            id: 777,
+            channel: Channel::Two,
+            action: Action::Beep,
+            intensity: 0,
+        };
+        let mut output = String::new();
+        shell.receive(&packet, &mut output).unwrap();
+        assert_eq!(output, "Learned ID 777 on channel 2\n");
+        assert_eq!(shell.id, 777);
+        assert!(!shell.is_listening());
+
+        // a learn without a remote expires
+        run(&mut shell, "learn 10");
+        let mut output = String::new();
+        shell.tick(Duration::from_secs(10), &mut output).unwrap();
+        assert_eq!(output, "");
+        shell.tick(Duration::from_secs(11), &mut output).unwrap();
+        assert_eq!(output, "No remote found, ID and channel are unchanged\n");
+    }
+
+    /// Test that replacing a collar cannot move its limits to another one without unlocking
+    #[test]
+    fn keeps_limits_of_replaced_collars() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        run(&mut shell, "unlock");
+        run(&mut shell, "collar add alice id=1234 ch=1 max=40");
+        run(&mut shell, "lock");
+        run(&mut shell, "id 7");
+
+        // missing settings are taken from the collar, not the configuration
+        assert_eq!(
+            run(&mut shell, "collar add alice"),
+            concat!(
+                "Saved collar alice: ID 1234 on channel 1, caixianlin, shock up to 40, ",
+                "vibrate up to 40, transmit up to 600000ms, 0ms between shocks\n"
+            )
+        );
+        assert_eq!(
+            run(&mut shell, "collar add alice id=1"),
+            "Limits are locked, run unlock first\n"
+        );
+        assert_eq!(shell.collars().get("alice").unwrap().id, 1234);
+        assert_eq!(
+            run(&mut shell, "send shock 50 @alice"),
+            "Shock intensity 50 exceeds the limit of 40\n"
+        );
+
+        run(&mut shell, "unlock");
+        run(&mut shell, "collar add alice id=1");
+        assert_eq!(shell.collars().get("alice").unwrap().id, 1);
+    }
+
+    /// Test saving collars and sending to them by name
+    #[test]
+    fn addresses_collars() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        run(&mut shell, "unlock");
+        assert_eq!(
+            run(&mut shell, "collar add alice id=1234 ch=1 max=40"),
+            concat!(
+                "Saved collar alice: ID 1234 on channel 1, caixianlin, shock up to 40, ",
+                "vibrate up to 40, transmit up to 600000ms, 0ms between shocks\n"
+            )
+        );
+        run(&mut shell, "lock");
+        run(&mut shell, "collar add bob id=5 protocol=petrainer");
+        assert_eq!(
+            run(&mut shell, "collar use bob"),
+            "Using collar bob: ID 5 on channel 0, petrainer\n"
+        );
+        run(&mut shell, "collar use alice");
+        assert!(run(&mut shell, "collar list").starts_with(concat!(
+            "Saved collars:\n",
+            "* alice: ID 1234 on channel 1, caixianlin, shock up to 40,"
+        )));
+
+        // the limit of the active collar applies
+        assert_eq!(
+            run(&mut shell, "send shock 50"),
+            "Shock intensity 50 exceeds the limit of 40\n"
+        );
+        assert!(shell.queue().is_empty());
+
+        // a target overrides the active collar for one command
+        assert_eq!(
+            run(&mut shell, "shock 50 @bob"),
+            "Sending Shock to shocker 5 on channel Zero with intensity 50\nRepeating 4 times\n"
+        );
+        assert_eq!(shell.id, 1234);
+        assert_eq!(
+            run(&mut shell, "beep @carol"),
+            "Unknown collar carol, see collar list\n"
+        );
+
+        // the address book and the active collar survive a restart
+        let shell = Shell::new(RecordingTransmitter::new(), shell.storage().clone());
+        assert_eq!(shell.collar.as_deref(), Some("alice"));
+        assert_eq!(shell.collars().len(), 2);
+
+        // changing the identity leaves the collar
+        let mut shell = shell;
+        run(&mut shell, "id 7");
+        assert_eq!(shell.collar, None);
+
+        // the limits survive a restart and removing them needs unlocking
+        assert_eq!(
+            run(&mut shell, "shock 50 @alice"),
+            "Shock intensity 50 exceeds the limit of 40\n"
+        );
+        assert_eq!(
+            run(&mut shell, "collar remove alice"),
+            "Limits are locked, run unlock first\n"
+        );
+        run(&mut shell, "unlock");
+        assert_eq!(
+            run(&mut shell, "collar remove alice"),
+            "Removed collar alice\n"
+        );
+        assert_eq!(
+            run(&mut shell, "collar remove alice"),
+            "Unknown collar alice, see collar list\n"
+        );
+    }
+
+    /// Test warning before shocks to a collar
+    #[test]
+    fn warns_before_shocks() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        run(&mut shell, "collar add alice id=5");
+        assert_eq!(
+            run(&mut shell, "collar warn alice beep delay=2s"),
+            "Warning of alice: beep for 500ms, then wait 2000ms\n"
+        );
+        assert_eq!(
+            run(&mut shell, "send shock 10 x1 @alice"),
+            concat!(
+                "Sending Shock to shocker 5 on channel Zero with intensity 10\n",
+                "Warning first: beep for 500ms, then wait 2000ms\n",
+                "Repeating 1 times\n"
+            )
+        );
+        assert_eq!(shell.queue().len(), 2);
+
+        // the warning applies to the collar, not only to its name
+        run(&mut shell, "stop");
+        run(&mut shell, "id 5");
+        run(&mut shell, "send shock 10 x1");
+        assert_eq!(shell.queue().len(), 2);
+
+        // the warning survives a restart and turning it off needs unlocking
+        let mut shell = Shell::new(RecordingTransmitter::new(), shell.storage().clone());
+        assert!(run(&mut shell, "collar list").contains("warning: beep for 500ms"));
+        assert_eq!(
+            run(&mut shell, "collar warn alice off"),
+            "Limits are locked, run unlock first\n"
+        );
+        assert_eq!(
+            run(&mut shell, "collar add alice id=6"),
+            "Limits are locked, run unlock first\n"
+        );
+        assert!(run(&mut shell, "collar add alice ch=0").starts_with("Saved collar alice"));
+        assert_eq!(
+            run(&mut shell, "collar remove alice"),
+            "Limits are locked, run unlock first\n"
+        );
+        assert!(shell.collars.get("alice").is_some());
+        run(&mut shell, "unlock");
+        assert_eq!(
+            run(&mut shell, "collar warn alice off"),
+            "Shocking alice without warning\n"
+        );
+        run(&mut shell, "send shock 10 x1 @alice");
+        assert_eq!(shell.queue().len(), 1);
+    }
+
+    /// Test saving and running sequences
+    #[test]
+    fn runs_macros() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        assert_eq!(
+            run(&mut shell, "macro"),
+            "No macros saved, add one with macro define <name> <steps>\n"
+        );
+        assert_eq!(
+            run(
+                &mut shell,
+                "macro define tease vibrate 30 pause 1s vibrate 60 beep 200ms"
+            ),
+            "Saved macro tease: vibrate 30 500ms pause 1000ms vibrate 60 500ms beep 200ms\n"
+        );
+        assert_eq!(
+            run(&mut shell, "macro run tease"),
+            "Running macro tease with 3 steps in 2200ms\n"
+        );
+        assert_eq!(shell.queue().len(), 3);
+        assert_eq!(
+            run(&mut shell, "macro run tease @alice"),
+            "Unknown collar alice, see collar list\n"
+        );
+
+        // the limits apply to every step
+        run(&mut shell, "unlock");
+        run(&mut shell, "limits vibrate=50");
+        assert_eq!(
+            run(&mut shell, "macro run tease"),
+            "Vibrate intensity 60 exceeds the limit of 50\n"
+        );
+        assert_eq!(shell.queue().len(), 3);
+
+        // the macros survive a restart
+        let mut shell = Shell::new(RecordingTransmitter::new(), shell.storage().clone());
+        assert_eq!(
+            run(&mut shell, "macro list"),
+            "Saved macros:\n  tease: vibrate 30 500ms pause 1000ms vibrate 60 500ms beep 200ms\n"
+        );
+        assert_eq!(
+            run(&mut shell, "macro delete tease"),
+            "Deleted macro tease\n"
+        );
+        assert_eq!(
+            run(&mut shell, "macro run tease"),
+            "Unknown macro tease, see macro list\n"
+        );
+    }
+
+    /// Test ramping the intensity
+    #[test]
+    fn ramps_intensity() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        assert_eq!(
+            run(&mut shell, "ramp vibrate 10 60 5s"),
+            "Ramping vibrate from 10 to 60 over 5000ms, linear in 50 steps\n"
+        );
+        assert_eq!(shell.queue().len(), 50);
+
+        // the caps apply to every step
+        run(&mut shell, "stop");
+        run(&mut shell, "unlock");
+        run(&mut shell, "limits vibrate=40 duration=10s");
+        assert_eq!(
+            run(&mut shell, "ramp vibrate 10 60 5s stepped"),
+            "Vibrate intensity 43 exceeds the limit of 40\n"
+        );
+        assert_eq!(
+            run(&mut shell, "ramp vibrate 10 40 20s exp"),
+            "Transmission of 10322ms exceeds the limit of 10000ms\n"
+        );
+        assert!(shell.queue().is_empty());
+
+        // the steps of a shock ramp are one shock for the cooldown
+        run(&mut shell, "limits cooldown=10s");
+        assert_eq!(
+            run(&mut shell, "ramp shock 10 30 2s"),
+            "Ramping shock from 10 to 30 over 2000ms, linear in 20 steps\n"
+        );
+        assert_eq!(shell.queue().len(), 20);
+    }
+
+    /// Test that a ramp to a warned collar is only warned of once
+    #[test]
+    fn warns_once_per_ramp() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        run(&mut shell, "collar add alice id=5");
+        run(&mut shell, "collar warn alice beep");
+        assert_eq!(
+            run(&mut shell, "ramp shock 10 20 2s @alice"),
+            "Ramping shock from 10 to 20 over 2000ms, linear in 11 steps\n"
+        );
+        // one beep before the first step, none in between
+        assert_eq!(shell.queue().len(), 12);
+    }
+
+    /// Test that random sessions fire until they end and respect the limits
+    #[test]
+    fn runs_random_sessions() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        assert_eq!(
+            run(
+                &mut shell,
+                "random start actions=v i=20 gap=1s-1s for=200ms session=5s seed=7"
+            ),
+            concat!(
+                "Starting random vibrate at intensity 20-20 for 200ms every 1000-1000ms ",
+                "for 5s with seed 7\n"
+            )
+        );
+        assert_eq!(
+            run(&mut shell, "random start"),
+            "A random session is running, stop it first\n"
+        );
+        assert_eq!(
+            run(&mut shell, "random"),
+            concat!(
+                "Random vibrate at intensity 20-20 for 200ms every 1000-1000ms, ",
+                "0 fired, 5s left, seed 7\n"
+            )
+        );
+
+        let mut output = String::new();
+        for millis in (1000..=6000).step_by(100) {
+            shell
+                .tick(Duration::from_millis(millis), &mut output)
+                .unwrap();
+        }
+        assert_eq!(
+            output,
+            concat!(
+                "Random Vibrate with intensity 20\n",
+                "Random Vibrate with intensity 20\n",
+                "Random Vibrate with intensity 20\n",
+                "Random Vibrate with intensity 20\n",
+                "Random session finished after 4 actions\n"
+            )
+        );
+        assert_eq!(run(&mut shell, "random"), "No random session is running\n");
+
+        // the bounds are checked against the limits when starting
+        run(&mut shell, "unlock");
+        run(&mut shell, "limits vibrate=15");
+        assert_eq!(
+            run(&mut shell, "random start actions=vibrate"),
+            "Vibrate intensity 30 exceeds the limit of 15\n"
+        );
+
+        // stopping ends the session
+        run(&mut shell, "random start actions=beep");
+        assert_eq!(
+            run(&mut shell, "stop"),
+            "Stopped, transmit queue is empty\nRandom session stopped\n"
+        );
+        assert_eq!(shell.status(Duration::ZERO).random, None);
+    }
+
+    /// Test changing and enforcing the limits
+    #[test]
+    fn enforces_limits() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        assert_eq!(
+            run(&mut shell, "limits shock=20"),
+            "Limits are locked, run unlock first\n"
+        );
+        assert_eq!(
+            run(&mut shell, "unlock"),
+            "Limits can be changed for the next 60 seconds\n"
+        );
+        assert_eq!(
+            run(&mut shell, "limits shock=20 cooldown=5s"),
+            concat!(
+                "Setting limits to shock up to 20, vibrate up to 99, ",
+                "transmit up to 600000ms, 5000ms between shocks\n"
+            )
+        );
+        run(&mut shell, "collar add alice id=5");
+        run(&mut shell, "limits @alice vibrate=30");
+        assert_eq!(
+            run(&mut shell, "limits"),
+            concat!(
+                "Limits: shock up to 20, vibrate up to 99, ",
+                "transmit up to 600000ms, 5000ms between shocks\n",
+                "Limits of alice: shock up to 99, vibrate up to 30, ",
+                "transmit up to 600000ms, 0ms between shocks\n"
+            )
+        );
+
+        // the unlock expires
+        let mut output = String::new();
+        let now = Duration::from_secs(1) + UNLOCK_TIMEOUT;
+        shell
+            .process_command("limits shock=99", now, &mut output)
+            .unwrap();
+        assert_eq!(output, "Limits are locked, run unlock first\n");
+        run(&mut shell, "unlock");
+        run(&mut shell, "lock");
+        assert_eq!(
+            run(&mut shell, "limits shock=99"),
+            "Limits are locked, run unlock first\n"
+        );
+
+        // global and collar limits both apply
+        assert_eq!(
+            run(&mut shell, "send shock 21"),
+            "Shock intensity 21 exceeds the limit of 20\n"
+        );
+        assert_eq!(
+            run(&mut shell, "vibrate 31 @alice"),
+            "Vibrate intensity 31 exceeds the limit of 30\n"
+        );
+        assert_eq!(
+            run(&mut shell, "transmit 65535"),
+            "Transmission of 3243982ms exceeds the limit of 600000ms\n"
+        );
+        run(&mut shell, "shock 20 @alice");
+        assert!(run(&mut shell, "shock 20 @alice").starts_with("Another shock to this collar"));
+
+        // the limits survive a restart
+        let mut shell = Shell::new(RecordingTransmitter::new(), shell.storage().clone());
+        assert_eq!(shell.status(Duration::ZERO).limits.max_shock, 20);
+        assert_eq!(
+            shell.process_request(
+                Request::Transmit {
+                    protocol: ProtocolKind::CaiXianLin,
+                    packet: Packet {
+                        id: 5,
+                        channel: Channel::Zero,
+                        action: Action::Shock,
+                        intensity: 50,
+                    },
+                    repeat: Repeat::Count(1),
+                },
+                Duration::ZERO
+            ),
+            Response::Error(ErrorCode::Limit)
+        );
+    }
+
+    /// Test executing binary requests
+    #[test]
+    fn processes_requests() {
+        let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+        let packet = Packet {
+            id: 42,
+            channel: Channel::Two,
+            action: Action::Vibrate,
+            intensity: 10,
+        };
+        let request = Request::SetConfig {
+            protocol: ProtocolKind::Petrainer,
+            packet: packet.clone(),
+        };
+        assert_eq!(
+            shell.process_request(request, Duration::ZERO),
+            Response::Ack
+        );
+        assert_eq!(shell.id, 42);
+
+        // petrainer has no channel 2
+        let request = Request::Transmit {
+            protocol: ProtocolKind::Petrainer,
+            packet,
+            repeat: Repeat::Count(1),
+        };
+        assert_eq!(
+            shell.process_request(request, Duration::ZERO),
+            Response::Error(ErrorCode::Encode)
+        );
+        assert!(shell.queue().is_empty());
+    }
+}
@@ -0,0 +1,235 @@
+use alloc::string::String;
+use core::{
+    fmt::{self, Display, Write},
+    time::Duration,
+};
+
+use crate::{
+    command::Mode,
+    json::Object,
+    limits::Limits,
+    packet::{Action, Channel},
+    protocol::ProtocolKind,
+};
+
+/// Progress of a random session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RandomState {
+    /// Seed to reproduce the session with
+    pub seed: u32,
+    /// Amount of actions fired so far
+    pub fired: u32,
+    /// Time until the session ends
+    pub remaining: Duration,
+}
+
+/// Snapshot of the configuration and activity of the device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    /// Firmware version
+    pub version: &'static str,
+    /// Time since boot
+    pub uptime: Duration,
+    /// Name of the saved collar in use
+    pub collar: Option<String>,
+    /// Shocker ID
+    pub id: u16,
+    /// Channel to transmit on
+    pub channel: Channel,
+    /// Protocol of the collar
+    pub protocol: ProtocolKind,
+    /// Action to perform
+    pub action: Action,
+    /// Intensity of the action
+    pub intensity: u8,
+    /// GPIO the receiver is connected to
+    pub rx_pin: u8,
+    /// Whether received frames are printed
+    pub sniffing: bool,
+    /// GPIO of the emergency stop button
+    pub stop_pin: Option<u8>,
+    /// Output format of the shell
+    pub mode: Mode,
+    /// Amount of jobs in the transmit queue
+    pub queue_depth: usize,
+    /// Whether a frame is being transmitted right now
+    pub transmitting: bool,
+    /// Limits of all transmissions
+    pub limits: Limits,
+    /// Random session in progress
+    pub random: Option<RandomState>,
+}
+
+impl Status {
+    /// Write the status as a single line JSON object
+    pub fn write_json(&self, output: &mut impl Write) -> fmt::Result {
+        let mut object = Object::new(output)?;
+        self.write_fields(&mut object)?;
+        object.finish()
+    }
+
+    /// Write the fields of the status into an existing JSON object
+    pub fn write_fields<W: Write>(&self, object: &mut Object<W>) -> fmt::Result {
+        object
+            .string("version", self.version)?
+            .number("uptime_ms", millis(self.uptime))?
+            .optional("collar", self.collar.as_deref(), Object::string)?
+            .number("id", self.id)?
+            .number("channel", self.channel as u8)?
+            .string("protocol", self.protocol.name())?
+            .string("action", self.action.name())?
+            .number("intensity", self.intensity)?
+            .number("rx_pin", self.rx_pin)?
+            .bool("sniffing", self.sniffing)?
+            .optional("stop_pin", self.stop_pin, Object::number)?
+            .string("mode", self.mode.name())?
+            .number(
+                "queue_depth",
+                i64::try_from(self.queue_depth).unwrap_or(i64::MAX),
+            )?
+            .bool("transmitting", self.transmitting)?
+            .object("limits", |object| {
+                object
+                    .number("max_shock", self.limits.max_shock)?
+                    .number("max_vibrate", self.limits.max_vibrate)?
+                    .number("max_duration_ms", millis(self.limits.max_duration))?
+                    .number("cooldown_ms", millis(self.limits.cooldown))?;
+                Ok(())
+            })?;
+        match &self.random {
+            Some(random) => object.object("random", |object| {
+                object
+                    .number("seed", random.seed)?
+                    .number("fired", random.fired)?
+                    .number("remaining_ms", millis(random.remaining))?;
+                Ok(())
+            })?,
+            None => object.null("random")?,
+        };
+        Ok(())
+    }
+}
+
+/// Duration in milliseconds for JSON
+fn millis(duration: Duration) -> i64 {
+    i64::try_from(duration.as_millis()).unwrap_or(i64::MAX)
+}
+
+impl Display for Status {
+    /// Print the status as a human readable table
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let uptime = self.uptime.as_secs();
+        writeln!(f, "Firmware    : {}", self.version)?;
+        writeln!(
+            f,
+            "Uptime      : {}h {:02}m {:02}s",
+            uptime / 3600,
+            uptime / 60 % 60,
+            uptime % 60
+        )?;
+        writeln!(
+            f,
+            "Collar      : {}",
+            self.collar.as_deref().unwrap_or("none")
+        )?;
+        writeln!(f, "ID          : {}", self.id)?;
+        writeln!(f, "Channel     : {}", self.channel as u8)?;
+        writeln!(f, "Protocol    : {}", self.protocol.name())?;
+        writeln!(f, "Action      : {}", self.action.name())?;
+        writeln!(f, "Intensity   : {}", self.intensity)?;
+        writeln!(
+            f,
+            "Receiver    : GPIO {}, sniffing {}",
+            self.rx_pin,
+            if self.sniffing { "on" } else { "off" }
+        )?;
+        match self.stop_pin {
+            Some(pin) => writeln!(f, "Stop button : GPIO {}", pin)?,
+            None => writeln!(f, "Stop button : off")?,
+        }
+        writeln!(f, "Mode        : {}", self.mode.name())?;
+        writeln!(f, "Limits      : {}", self.limits)?;
+        match &self.random {
+            Some(random) => writeln!(
+                f,
+                "Random      : {} fired, {}s left, seed {}",
+                random.fired,
+                random.remaining.as_secs(),
+                random.seed
+            )?,
+            None => writeln!(f, "Random      : off")?,
+        }
+        write!(
+            f,
+            "Queue       : {} jobs, {}",
+            self.queue_depth,
+            if self.transmitting {
+                "transmitting"
+            } else {
+                "idle"
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn status() -> Status {
+        Status {
+            version: "0.1.0",
+            uptime: Duration::from_millis(3_723_500),
+            collar: Some("alice".to_string()),
+            id: 1234,
+            channel: Channel::One,
+            protocol: ProtocolKind::CaiXianLin,
+            action: Action::Vibrate,
+            intensity: 30,
+            rx_pin: 1,
+            sniffing: false,
+            stop_pin: Some(9),
+            mode: Mode::Text,
+            queue_depth: 2,
+            transmitting: true,
+            limits: Limits {
+                max_shock: 40,
+                ..Limits::NONE
+            },
+            random: Some(RandomState {
+                seed: 42,
+                fired: 3,
+                remaining: Duration::from_millis(90_500),
+            }),
+        }
+    }
+
+    /// Test the JSON representation of the status
+    #[test]
+    fn writes_json() {
+        let mut output = String::new();
+        status().write_json(&mut output).unwrap();
+        assert_eq!(
+            output,
+            concat!(
+                r#"{"version":"0.1.0","uptime_ms":3723500,"collar":"alice","id":1234,"channel":1,"#,
+                r#""protocol":"caixianlin","action":"vibrate","intensity":30,"rx_pin":1,"#,
+                r#""sniffing":false,"stop_pin":9,"mode":"text","queue_depth":2,"transmitting":true,"#,
+                r#""limits":{"max_shock":40,"max_vibrate":99,"max_duration_ms":600000,"cooldown_ms":0},"#,
+                r#""random":{"seed":42,"fired":3,"remaining_ms":90500}}"#
+            )
+        );
+    }
+
+    /// Test the human readable representation of the status
+    #[test]
+    fn prints_table() {
+        let text = status().to_string();
+        assert!(text.contains("Uptime      : 1h 02m 03s\n"));
+        assert!(text.contains("Collar      : alice\n"));
+        assert!(text.contains("Stop button : GPIO 9\n"));
+        assert!(text.contains("Random      : 3 fired, 90s left, seed 42\n"));
+        assert!(text.ends_with("Queue       : 2 jobs, transmitting"));
+    }
+}
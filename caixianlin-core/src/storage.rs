@@ -0,0 +1,82 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// Persistent key value storage for the configuration
+///
+/// Failing writes are not reported, the configuration only lives in memory then.
+pub trait Storage {
+    /// Read a byte
+    fn get_u8(&self, key: &str) -> Option<u8>;
+
+    /// Read a 16 bit number
+    fn get_u16(&self, key: &str) -> Option<u16>;
+
+    /// Store a byte
+    fn set_u8(&mut self, key: &str, value: u8);
+
+    /// Store a 16 bit number
+    fn set_u16(&mut self, key: &str, value: u16);
+
+    /// Read a blob of bytes
+    fn get_blob(&self, key: &str) -> Option<Vec<u8>>;
+
+    /// Store a blob of bytes
+    fn set_blob(&mut self, key: &str, value: &[u8]);
+
+    /// Remove a value
+    fn remove(&mut self, key: &str);
+}
+
+/// Storage that only lives in memory
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryStorage {
+    /// Stored values, numbers are little endian
+    values: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    /// Create a new empty storage
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the raw bytes of a value
+    fn get<const N: usize>(&self, key: &str) -> Option<[u8; N]> {
+        self.values.get(key)?.as_slice().try_into().ok()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get_u8(&self, key: &str) -> Option<u8> {
+        self.get::<1>(key).map(u8::from_le_bytes)
+    }
+
+    fn get_u16(&self, key: &str) -> Option<u16> {
+        self.get::<2>(key).map(u16::from_le_bytes)
+    }
+
+    fn set_u8(&mut self, key: &str, value: u8) {
+        self.values
+            .insert(key.to_string(), value.to_le_bytes().to_vec());
+    }
+
+    fn set_u16(&mut self, key: &str, value: u16) {
+        self.values
+            .insert(key.to_string(), value.to_le_bytes().to_vec());
+    }
+
+    fn get_blob(&self, key: &str) -> Option<Vec<u8>> {
+        self.values.get(key).cloned()
+    }
+
+    fn set_blob(&mut self, key: &str, value: &[u8]) {
+        self.values.insert(key.to_string(), value.to_vec());
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.values.remove(key);
+    }
+}
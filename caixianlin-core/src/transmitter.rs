@@ -0,0 +1,61 @@
+use alloc::vec::Vec;
+
+use crate::protocol::Timing;
+
+/// Hardware that can transmit sequences of pulses
+pub trait Transmitter {
+    /// Start transmitting the given symbols
+    ///
+    /// This is only called while the transmitter is not busy.
+    fn transmit(&mut self, symbols: &[Timing]);
+
+    /// Whether the last transmission is still in progress
+    fn is_busy(&self) -> bool;
+
+    /// Abort the transmission in progress
+    fn abort(&mut self);
+}
+
+/// Transmitter that records everything it is asked to transmit
+///
+/// Each transmission keeps the transmitter busy until [`RecordingTransmitter::finish`] is called.
+#[derive(Debug, Default)]
+pub struct RecordingTransmitter {
+    /// All transmissions in the order they were started
+    pub transmissions: Vec<Vec<Timing>>,
+    /// Amount of transmissions that were aborted
+    pub aborted: usize,
+    /// Whether a transmission is in progress
+    busy: bool,
+}
+
+impl RecordingTransmitter {
+    /// Create a new recording transmitter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Complete the transmission in progress
+    pub fn finish(&mut self) {
+        self.busy = false;
+    }
+}
+
+impl Transmitter for RecordingTransmitter {
+    fn transmit(&mut self, symbols: &[Timing]) {
+        assert!(!self.busy, "transmission started while busy");
+        self.transmissions.push(symbols.to_vec());
+        self.busy = true;
+    }
+
+    fn is_busy(&self) -> bool {
+        self.busy
+    }
+
+    fn abort(&mut self) {
+        if self.busy {
+            self.aborted += 1;
+        }
+        self.busy = false;
+    }
+}
@@ -0,0 +1,118 @@
+use core::{
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use crate::packet::{Action, Packet};
+
+/// Longest warning burst
+pub const MAX_WARNING_DURATION: Duration = Duration::from_secs(10);
+
+/// Longest pause between the warning and the shock
+pub const MAX_WARNING_DELAY: Duration = Duration::from_secs(10);
+
+/// Length of the warning burst if none is given
+pub const DEFAULT_WARNING_DURATION: Duration = Duration::from_millis(500);
+
+/// Pause between the warning and the shock if none is given
+pub const DEFAULT_WARNING_DELAY: Duration = Duration::from_secs(1);
+
+/// Length of [`Warning::encode`]
+pub const ENCODED_WARNING_LENGTH: usize = 6;
+
+/// Beep or vibrate burst that precedes every shock to a collar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Warning {
+    /// Action of the burst, either beep or vibrate
+    pub action: Action,
+    /// Intensity of the burst, only used for vibrate
+    pub intensity: u8,
+    /// How long the burst lasts
+    pub duration: Duration,
+    /// Pause between the end of the burst and the shock
+    pub delay: Duration,
+}
+
+impl Warning {
+    /// Packet of the burst that warns of the given shock
+    pub fn packet(&self, shock: &Packet) -> Packet {
+        Packet {
+            action: self.action,
+            intensity: self.intensity,
+            ..*shock
+        }
+    }
+
+    /// Serialize the warning for storage
+    ///
+    /// The format is `action, intensity, duration in ms (u16 little endian), delay in ms (u16
+    /// little endian)`. All zeros are never a valid warning and can be used for none.
+    pub fn encode(&self) -> [u8; ENCODED_WARNING_LENGTH] {
+        let [d0, d1] = (self.duration.as_millis() as u16).to_le_bytes();
+        let [p0, p1] = (self.delay.as_millis() as u16).to_le_bytes();
+        [self.action as u8, self.intensity, d0, d1, p0, p1]
+    }
+
+    /// Read a warning written by [`Warning::encode`], `None` if it is out of range
+    pub fn decode(bytes: &[u8; ENCODED_WARNING_LENGTH]) -> Option<Self> {
+        let [action, intensity, d0, d1, p0, p1] = *bytes;
+        let warning = Warning {
+            action: Action::try_from(action).ok()?,
+            intensity,
+            duration: Duration::from_millis(u16::from_le_bytes([d0, d1]) as u64),
+            delay: Duration::from_millis(u16::from_le_bytes([p0, p1]) as u64),
+        };
+        let valid = matches!(warning.action, Action::Beep | Action::Vibrate)
+            && intensity <= 99
+            && !warning.duration.is_zero()
+            && warning.duration <= MAX_WARNING_DURATION
+            && warning.delay <= MAX_WARNING_DELAY;
+        valid.then_some(warning)
+    }
+}
+
+impl Display for Warning {
+    /// Describe the warning in a sentence
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.action {
+            Action::Vibrate => write!(f, "vibrate {}", self.intensity)?,
+            action => write!(f, "{}", action.name())?,
+        }
+        write!(
+            f,
+            " for {}ms, then wait {}ms",
+            self.duration.as_millis(),
+            self.delay.as_millis()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    /// Test that the stored format round trips and rejects garbage
+    #[test]
+    fn encodes_warnings() {
+        let warning = Warning {
+            action: Action::Vibrate,
+            intensity: 20,
+            duration: Duration::from_millis(800),
+            delay: Duration::from_secs(2),
+        };
+        assert_eq!(Warning::decode(&warning.encode()), Some(warning));
+        assert_eq!(
+            warning.to_string(),
+            "vibrate 20 for 800ms, then wait 2000ms"
+        );
+
+        assert_eq!(Warning::decode(&[0; ENCODED_WARNING_LENGTH]), None);
+        let mut bytes = warning.encode();
+        bytes[0] = Action::Shock as u8;
+        assert_eq!(Warning::decode(&bytes), None);
+        let mut bytes = warning.encode();
+        bytes[4..6].copy_from_slice(&20000u16.to_le_bytes());
+        assert_eq!(Warning::decode(&bytes), None);
+    }
+}
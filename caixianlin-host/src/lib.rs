@@ -0,0 +1,401 @@
+//! Control the serialcaixianlin firmware from a computer.
+//!
+//! [`Device`] switches the console of the firmware to JSON lines and wraps the text commands in a
+//! typed API:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! let mut device = caixianlin_host::Device::open("/dev/ttyACM0")?;
+//! device.set_id(1234)?;
+//! device.shock(20, Duration::from_millis(500))?;
+//! # Ok::<(), caixianlin_host::Error>(())
+//! ```
+use std::{
+    fmt::{self, Display},
+    io::{self, Read, Write},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use serialport::SerialPort;
+use thiserror::Error;
+
+/// Baud rate of the console
+pub const BAUD_RATE: u32 = 115_200;
+
+/// Time to wait for the reply to a command
+pub const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Ctrl-C, discards whatever was typed on the console before
+const CANCEL: u8 = 0x03;
+
+/// Errors that can occur while talking to a device
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Cannot open serial port: {0}")]
+    Serial(#[from] serialport::Error),
+    #[error("Cannot talk to device: {0}")]
+    Io(#[from] io::Error),
+    #[error("Device sent an invalid reply: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Device did not reply within {}ms", TIMEOUT.as_millis())]
+    Timeout,
+    #[error("{message} ({code})")]
+    Device { code: String, message: String },
+}
+
+/// Configuration and activity of the device, as reported with every reply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Status {
+    /// Firmware version
+    pub version: String,
+    /// Time since boot in milliseconds
+    pub uptime_ms: u64,
+    /// Name of the saved collar in use
+    #[serde(default)]
+    pub collar: Option<String>,
+    /// Shocker ID
+    pub id: u16,
+    /// Channel to transmit on
+    pub channel: u8,
+    /// Protocol of the collar
+    pub protocol: String,
+    /// Action to perform
+    pub action: String,
+    /// Intensity of the action
+    pub intensity: u8,
+    /// GPIO the receiver is connected to
+    pub rx_pin: u8,
+    /// Whether received frames are printed
+    pub sniffing: bool,
+    /// GPIO of the emergency stop button
+    pub stop_pin: Option<u8>,
+    /// Output format of the console
+    pub mode: String,
+    /// Amount of jobs in the transmit queue
+    pub queue_depth: usize,
+    /// Whether a frame is being transmitted right now
+    pub transmitting: bool,
+}
+
+impl Display for Status {
+    /// Print the status as a human readable table
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Firmware    : {}", self.version)?;
+        writeln!(f, "Uptime      : {}s", self.uptime_ms / 1000)?;
+        writeln!(
+            f,
+            "Collar      : {}",
+            self.collar.as_deref().unwrap_or("none")
+        )?;
+        writeln!(f, "ID          : {}", self.id)?;
+        writeln!(f, "Channel     : {}", self.channel)?;
+        writeln!(f, "Protocol    : {}", self.protocol)?;
+        writeln!(f, "Action      : {}", self.action)?;
+        writeln!(f, "Intensity   : {}", self.intensity)?;
+        write!(
+            f,
+            "Queue       : {} jobs, {}",
+            self.queue_depth,
+            if self.transmitting {
+                "transmitting"
+            } else {
+                "idle"
+            }
+        )
+    }
+}
+
+/// Successful reply to a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reply {
+    /// Human readable description of what happened
+    pub message: String,
+    /// State of the device after the command
+    pub state: Status,
+}
+
+/// Reply as it is sent by the device
+#[derive(Deserialize)]
+struct RawReply {
+    ok: bool,
+    code: Option<String>,
+    message: String,
+    state: Status,
+}
+
+/// Device running the serialcaixianlin firmware
+///
+/// The console is switched to JSON lines while the device is open and switched back to text
+/// mode when it is dropped, if that is where it was before.
+pub struct Device<P: Read + Write> {
+    /// Connection to the console
+    port: P,
+    /// Bytes received after the last complete line
+    buffer: Vec<u8>,
+    /// Whether the console was in text mode before it was opened
+    restore_text: bool,
+}
+
+impl Device<Box<dyn SerialPort>> {
+    /// Open the device on a serial port like `/dev/ttyACM0`
+    pub fn open(path: &str) -> Result<Self, Error> {
+        let port = serialport::new(path, BAUD_RATE)
+            .timeout(Duration::from_millis(100))
+            .open()?;
+        Self::new(port)
+    }
+}
+
+impl<P: Read + Write> Device<P> {
+    /// Take over the console on an already opened connection
+    ///
+    /// The connection should time out reads, otherwise a silent device blocks forever.
+    pub fn new(port: P) -> Result<Self, Error> {
+        let mut device = Self {
+            port,
+            buffer: Vec::new(),
+            restore_text: false,
+        };
+
+        // discard a half typed line and find out which mode the console is in
+        device.port.write_all(&[CANCEL])?;
+        device.write_line("mode")?;
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let line = device.read_line(deadline)?;
+            if line == "Mode is text" {
+                device.command("mode json")?;
+                device.restore_text = true;
+                break;
+            }
+            if line.starts_with('{') && device.parse(&line)?.is_some() {
+                break;
+            }
+        }
+        Ok(device)
+    }
+
+    /// Execute a text command, failing if the device rejects it
+    pub fn command(&mut self, line: &str) -> Result<Reply, Error> {
+        self.write_line(line)?;
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let line = self.read_line(deadline)?;
+            // skip echoes and boot messages
+            if !line.starts_with('{') {
+                continue;
+            }
+            if let Some(reply) = self.parse(&line)? {
+                return reply;
+            }
+        }
+    }
+
+    /// Set the shocker ID
+    pub fn set_id(&mut self, id: u16) -> Result<Reply, Error> {
+        self.command(&format!("id {}", id))
+    }
+
+    /// Set the channel
+    pub fn set_channel(&mut self, channel: u8) -> Result<Reply, Error> {
+        self.command(&format!("channel {}", channel))
+    }
+
+    /// Set the protocol of the collar
+    pub fn set_protocol(&mut self, protocol: &str) -> Result<Reply, Error> {
+        self.command(&format!("protocol {}", protocol))
+    }
+
+    /// Configure id, channel and protocol of a saved collar
+    pub fn use_collar(&mut self, name: &str) -> Result<Reply, Error> {
+        self.command(&format!("collar use {}", name))
+    }
+
+    /// Shock with the given intensity for a while, without changing the configured action
+    pub fn shock(&mut self, intensity: u8, duration: Duration) -> Result<Reply, Error> {
+        self.send("shock", Some(intensity), duration)
+    }
+
+    /// Vibrate with the given intensity for a while, without changing the configured action
+    pub fn vibrate(&mut self, intensity: u8, duration: Duration) -> Result<Reply, Error> {
+        self.send("vibrate", Some(intensity), duration)
+    }
+
+    /// Beep for a while, without changing the configured action
+    pub fn beep(&mut self, duration: Duration) -> Result<Reply, Error> {
+        self.send("beep", None, duration)
+    }
+
+    /// Stop transmitting and clear the queue
+    pub fn stop(&mut self) -> Result<Reply, Error> {
+        self.command("stop")
+    }
+
+    /// Read the configuration and activity of the device
+    pub fn status(&mut self) -> Result<Status, Error> {
+        Ok(self.command("status")?.state)
+    }
+
+    /// Queue a timed transmission of an action
+    fn send(
+        &mut self,
+        action: &str,
+        intensity: Option<u8>,
+        duration: Duration,
+    ) -> Result<Reply, Error> {
+        let intensity = intensity.map(|intensity| format!(" {}", intensity));
+        self.command(&format!(
+            "send {}{} for={}ms",
+            action,
+            intensity.unwrap_or_default(),
+            duration.as_millis()
+        ))
+    }
+
+    /// Parse a JSON line, returning `None` for asynchronous events
+    fn parse(&self, line: &str) -> Result<Option<Result<Reply, Error>>, Error> {
+        let value: serde_json::Value = serde_json::from_str(line)?;
+        if value.get("event").is_some() {
+            return Ok(None);
+        }
+        let reply: RawReply = serde_json::from_value(value)?;
+        if !reply.ok {
+            return Ok(Some(Err(Error::Device {
+                code: reply.code.unwrap_or_default(),
+                message: reply.message,
+            })));
+        }
+        Ok(Some(Ok(Reply {
+            message: reply.message,
+            state: reply.state,
+        })))
+    }
+
+    /// Send a line to the console
+    fn write_line(&mut self, line: &str) -> Result<(), Error> {
+        self.port.write_all(line.as_bytes())?;
+        self.port.write_all(b"\n")?;
+        self.port.flush()?;
+        Ok(())
+    }
+
+    /// Read the next non empty line, without its line ending
+    fn read_line(&mut self, deadline: Instant) -> Result<String, Error> {
+        loop {
+            if let Some(end) = self.buffer.iter().position(|&byte| byte == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line).trim().to_string();
+                if line.is_empty() {
+                    continue;
+                }
+                return Ok(line);
+            }
+
+            if Instant::now() >= deadline {
+                return Err(Error::Timeout);
+            }
+            let mut chunk = [0; 256];
+            match self.port.read(&mut chunk) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(length) => self.buffer.extend_from_slice(&chunk[..length]),
+                Err(error)
+                    if matches!(
+                        error.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::Interrupted
+                    ) => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+    }
+}
+
+impl<P: Read + Write> Drop for Device<P> {
+    fn drop(&mut self) {
+        if self.restore_text {
+            // the reply is in text mode already, and the device may be gone anyway
+            self.write_line("mode text").ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{Read, Write},
+        thread,
+    };
+
+    use caixianlin_core::{
+        line::LineEditor, shell::Shell, storage::MemoryStorage, transmitter::RecordingTransmitter,
+    };
+    use nix::{pty::openpty, unistd::ttyname};
+
+    use super::*;
+
+    /// Run the shell of the firmware on the other end of a pseudo terminal
+    ///
+    /// Returns the path of the terminal and the file keeping it open.
+    fn loopback() -> (String, File) {
+        let pty = openpty(None, None).unwrap();
+        let path = ttyname(&pty.slave).unwrap();
+        let mut master = File::from(pty.master);
+
+        thread::spawn(move || {
+            let mut shell = Shell::new(RecordingTransmitter::new(), MemoryStorage::new());
+            let mut editor = LineEditor::new();
+            let mut output = String::new();
+            let mut byte = [0];
+            // ends with an error once all ends of the terminal are closed
+            while master.read_exact(&mut byte).is_ok() {
+                let line = editor.push(byte[0], &mut output).unwrap();
+                if shell.mode != caixianlin_core::command::Mode::Text {
+                    output.clear();
+                }
+                if let Some(line) = line {
+                    shell
+                        .process_command(&line, Duration::ZERO, &mut output)
+                        .unwrap();
+                }
+                if master.write_all(output.as_bytes()).is_err() {
+                    break;
+                }
+                output.clear();
+            }
+        });
+
+        (path.to_string_lossy().into_owned(), File::from(pty.slave))
+    }
+
+    /// Test the typed API against the firmware logic
+    #[test]
+    fn controls_device() {
+        let (path, _slave) = loopback();
+        let mut device = Device::open(&path).unwrap();
+
+        let reply = device.set_id(1234).unwrap();
+        assert_eq!(reply.message, "Setting ID to 1234");
+        assert_eq!(reply.state.id, 1234);
+        assert_eq!(reply.state.mode, "json");
+
+        let reply = device.shock(20, Duration::from_millis(1500)).unwrap();
+        assert_eq!(
+            reply.message,
+            "Sending Shock to shocker 1234 on channel Zero with intensity 20\nRepeating for 1500ms, 49500us per frame"
+        );
+        assert_eq!(reply.state.queue_depth, 1);
+        // the configured action is left alone
+        assert_eq!(reply.state.action, "shock");
+        assert_eq!(reply.state.intensity, 1);
+
+        device.stop().unwrap();
+        assert_eq!(device.status().unwrap().queue_depth, 0);
+
+        match device.set_channel(3) {
+            Err(Error::Device { code, .. }) => assert_eq!(code, "out_of_range"),
+            other => panic!("expected a rejection, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::{process::ExitCode, time::Duration};
+
+use caixianlin_core::command::parse_duration;
+use caixianlin_host::{Device, Error, Reply};
+use clap::{Parser, Subcommand};
+
+/// Control a collar through the serialcaixianlin firmware
+#[derive(Parser)]
+#[command(version, about)]
+struct Arguments {
+    /// Serial port of the device
+    #[arg(short, long, default_value = "/dev/ttyACM0")]
+    port: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Set the shocker ID
+    Id { id: u16 },
+    /// Set the channel
+    Channel {
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=2))]
+        channel: u8,
+    },
+    /// Set the protocol of the collar
+    Protocol { name: String },
+    /// Configure id, channel and protocol of a saved collar
+    Use { name: String },
+    /// Shock for a while
+    Shock {
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=99))]
+        intensity: u8,
+        /// Duration like 1500ms or 2s
+        #[arg(default_value = "1s", value_parser = duration)]
+        duration: Duration,
+    },
+    /// Vibrate for a while
+    Vibrate {
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=99))]
+        intensity: u8,
+        /// Duration like 1500ms or 2s
+        #[arg(default_value = "1s", value_parser = duration)]
+        duration: Duration,
+    },
+    /// Beep for a while
+    Beep {
+        /// Duration like 1500ms or 2s
+        #[arg(default_value = "1s", value_parser = duration)]
+        duration: Duration,
+    },
+    /// Stop transmitting and clear the queue
+    Stop,
+    /// Print the configuration and activity of the device
+    Status {
+        /// Print the status as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Execute any console command
+    Raw {
+        #[arg(required = true, trailing_var_arg = true)]
+        command: Vec<String>,
+    },
+}
+
+/// Parse a duration argument
+fn duration(argument: &str) -> Result<Duration, String> {
+    parse_duration(argument).ok_or_else(|| "expected a duration like 1500ms or 2s".to_string())
+}
+
+fn main() -> ExitCode {
+    let arguments = Arguments::parse();
+    match run(arguments) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Execute the command line
+fn run(arguments: Arguments) -> Result<(), Error> {
+    let mut device = Device::open(&arguments.port)?;
+    let reply = match arguments.command {
+        Command::Id { id } => device.set_id(id)?,
+        Command::Channel { channel } => device.set_channel(channel)?,
+        Command::Protocol { name } => device.set_protocol(&name)?,
+        Command::Use { name } => device.use_collar(&name)?,
+        Command::Shock {
+            intensity,
+            duration,
+        } => device.shock(intensity, duration)?,
+        Command::Vibrate {
+            intensity,
+            duration,
+        } => device.vibrate(intensity, duration)?,
+        Command::Beep { duration } => device.beep(duration)?,
+        Command::Stop => device.stop()?,
+        Command::Status { json: false } => {
+            println!("{}", device.status()?);
+            return Ok(());
+        }
+        Command::Status { json: true } => {
+            println!("{}", serde_json::to_string_pretty(&device.status()?)?);
+            return Ok(());
+        }
+        Command::Raw { command } => device.command(&command.join(" "))?,
+    };
+    print_reply(&reply);
+    Ok(())
+}
+
+/// Print the message of a reply
+fn print_reply(reply: &Reply) {
+    if !reply.message.is_empty() {
+        println!("{}", reply.message);
+    }
+}
@@ -0,0 +1,16 @@
+use std::io::{stdout, Write};
+
+use esp_idf_sys::uart_write_bytes;
+
+/// Send a frame on UART0
+///
+/// The frame is written to the driver directly, because stdout would turn line feeds into
+/// carriage return and line feed.
+pub fn write_frame(frame: &[u8]) {
+    // keep text printed before in order
+    stdout().flush().ok();
+    let written = unsafe { uart_write_bytes(0, frame.as_ptr().cast(), frame.len()) };
+    if written < 0 {
+        log::warn!("Failed to write binary frame");
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    sync::{
+        mpsc::{channel, sync_channel, Receiver, SyncSender},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use caixianlin_core::{
+    binary::{ErrorCode, StatusReport},
+    command::Command,
+    gatt::Characteristic,
+    line::LineEditor,
+};
+use esp32_nimble::{
+    enums::{AuthReq, SecurityIOCap},
+    utilities::{mutex::Mutex, BleUuid},
+    uuid128, BLEAdvertisementData, BLECharacteristic, BLEDevice, BLEError, NimbleProperties,
+};
+
+use crate::Firmware;
+
+/// Name in the advertisement, longer names do not fit next to a 128 bit service UUID
+const DEVICE_NAME: &str = "CaiXian";
+
+/// First ATT error code reserved for applications, the binary error code is added to it
+const ATT_APPLICATION_ERROR: u8 = 0x80;
+
+/// ATT error for writes that could not be executed in time
+const ATT_UNLIKELY_ERROR: u8 = 0x0e;
+
+/// Longest time a write waits for the main loop to execute it
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// UUID of the control service
+const SERVICE_UUID: BleUuid = uuid128!("ca1c0001-5e7a-4b10-9a4e-3c0f8d2e6b71");
+
+/// UUID of the Nordic UART Service
+const UART_SERVICE_UUID: BleUuid = uuid128!("6E400001-B5A3-F393-E0A9-E50E24DCCA9E");
+
+/// UUID of the characteristic that clients write text to
+const UART_RX_UUID: BleUuid = uuid128!("6E400002-B5A3-F393-E0A9-E50E24DCCA9E");
+
+/// UUID of the characteristic that notifies clients of text
+const UART_TX_UUID: BleUuid = uuid128!("6E400003-B5A3-F393-E0A9-E50E24DCCA9E");
+
+/// Longest notification, the payload that fits into the smallest ATT MTU
+const UART_CHUNK_LENGTH: usize = 20;
+
+/// Most notifications sent per poll, so long replies do not exhaust the NimBLE buffers
+const UART_CHUNKS_PER_POLL: usize = 4;
+
+/// Advertising of the services, following the passkey of the shell
+///
+/// The services have to be created before, because the server starts with the advertising.
+#[derive(Default)]
+pub struct Advertising {
+    /// Passkey the services are advertised with
+    passkey: Option<u32>,
+}
+
+impl Advertising {
+    /// Start, stop or restart advertising when the passkey of the shell changed
+    pub fn poll(&mut self, shell: &Firmware) {
+        if shell.passkey == self.passkey {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.passkey, shell.passkey);
+        if let Err(error) = advertise(previous, shell.passkey) {
+            log::warn!("Failed to update BLE advertising: {}", error);
+        }
+    }
+}
+
+/// Replace the passkey of the services and advertise them if there is one
+fn advertise(previous: Option<u32>, passkey: Option<u32>) -> Result<(), BLEError> {
+    let device = BLEDevice::take();
+    let advertising = device.get_advertising();
+    let server = device.get_server();
+    server.advertise_on_disconnect(passkey.is_some());
+    if previous.is_some() {
+        // clients that paired with the previous passkey lose their access
+        advertising.lock().stop()?;
+        let connections: Vec<_> = server
+            .connections()
+            .map(|connection| connection.conn_handle())
+            .collect();
+        for connection in connections {
+            server.disconnect(connection)?;
+        }
+        device.delete_all_bonds()?;
+    }
+    let Some(passkey) = passkey else {
+        return Ok(());
+    };
+
+    // writes need an encrypted link to a client that entered the passkey
+    device
+        .security()
+        .set_auth(AuthReq::Bond | AuthReq::Mitm | AuthReq::Sc)
+        .set_passkey(passkey)
+        .set_io_cap(SecurityIOCap::DisplayOnly);
+    // generic terminal apps look for the UART service, so it goes into the advertisement
+    advertising.lock().set_data(
+        BLEAdvertisementData::new()
+            .name(DEVICE_NAME)
+            .add_service_uuid(UART_SERVICE_UUID),
+    )?;
+    advertising
+        .lock()
+        .scan_response_data(BLEAdvertisementData::new().add_service_uuid(SERVICE_UUID))?;
+    advertising.lock().start()
+}
+
+/// UUID of a characteristic of the control service
+fn uuid(characteristic: Characteristic) -> BleUuid {
+    match characteristic {
+        Characteristic::Id => uuid128!("ca1c0002-5e7a-4b10-9a4e-3c0f8d2e6b71"),
+        Characteristic::Channel => uuid128!("ca1c0003-5e7a-4b10-9a4e-3c0f8d2e6b71"),
+        Characteristic::Action => uuid128!("ca1c0004-5e7a-4b10-9a4e-3c0f8d2e6b71"),
+        Characteristic::Intensity => uuid128!("ca1c0005-5e7a-4b10-9a4e-3c0f8d2e6b71"),
+        Characteristic::Transmit => uuid128!("ca1c0006-5e7a-4b10-9a4e-3c0f8d2e6b71"),
+        Characteristic::Stop => uuid128!("ca1c0007-5e7a-4b10-9a4e-3c0f8d2e6b71"),
+        Characteristic::Status => uuid128!("ca1c0008-5e7a-4b10-9a4e-3c0f8d2e6b71"),
+    }
+}
+
+/// Command written by a client, waiting for the main loop
+struct PendingWrite {
+    /// Command of the written value
+    command: Command,
+    /// When the value was written
+    written: Instant,
+    /// Answers the write with the outcome of the command
+    result: SyncSender<Result<(), ErrorCode>>,
+}
+
+/// GATT service controlling the shell over BLE
+///
+/// Writes arrive on the NimBLE host task and are passed to the main loop, which executes them on
+/// the same shell as the serial commands and keeps the readable values up to date. The host task
+/// waits for the outcome, so failed commands are rejected with their error code.
+pub struct ControlService {
+    /// Registered characteristics
+    characteristics: Vec<(Characteristic, Arc<Mutex<BLECharacteristic>>)>,
+    /// Commands written by clients
+    writes: Receiver<PendingWrite>,
+    /// Last published status, without the uptime
+    published: Option<StatusReport>,
+}
+
+impl ControlService {
+    /// Register the service, it is served once [`Advertising`] has a passkey
+    pub fn new() -> Self {
+        let device = BLEDevice::take();
+        let service = device.get_server().create_service(SERVICE_UUID);
+        let (sender, writes) = channel();
+
+        let mut characteristics = Vec::new();
+        for characteristic in Characteristic::ALL {
+            let mut properties = NimbleProperties::empty();
+            if characteristic.is_readable() {
+                properties |= NimbleProperties::READ;
+            }
+            if characteristic.is_writable() {
+                // writes can transmit, so only paired clients may do them
+                properties |= NimbleProperties::WRITE
+                    | NimbleProperties::WRITE_ENC
+                    | NimbleProperties::WRITE_AUTHEN;
+            }
+            if characteristic.notifies() {
+                properties |= NimbleProperties::NOTIFY;
+            }
+            let handle = service
+                .lock()
+                .create_characteristic(uuid(characteristic), properties);
+            if characteristic.is_writable() {
+                let sender = sender.clone();
+                handle.lock().on_write(move |args| {
+                    let command = match characteristic.write(args.recv_data()) {
+                        Ok(command) => command,
+                        Err(error) => {
+                            args.reject_with_error_code(ATT_APPLICATION_ERROR + error as u8);
+                            return;
+                        }
+                    };
+                    // the main loop owns the shell, it picks the command up on its next poll
+                    let (result, outcome) = sync_channel(1);
+                    let write = PendingWrite {
+                        command,
+                        written: Instant::now(),
+                        result,
+                    };
+                    sender.send(write).ok();
+                    match outcome.recv_timeout(WRITE_TIMEOUT) {
+                        Ok(Ok(())) => {}
+                        Ok(Err(error)) => {
+                            args.reject_with_error_code(ATT_APPLICATION_ERROR + error as u8)
+                        }
+                        Err(_) => args.reject_with_error_code(ATT_UNLIKELY_ERROR),
+                    }
+                });
+            }
+            characteristics.push((characteristic, handle));
+        }
+
+        Self {
+            characteristics,
+            writes,
+            published: None,
+        }
+    }
+
+    /// Execute the written commands and publish changes of the status
+    pub fn poll(&mut self, shell: &mut Firmware, now: Duration) {
+        for write in self.writes.try_iter() {
+            // the write is about to be rejected, executing it anyway would surprise the client
+            if write.written.elapsed() > WRITE_TIMEOUT / 2 {
+                continue;
+            }
+            // the client learns the outcome from the answer to its write, the reply text is only
+            // meant for the console
+            let result = shell.process_parsed(write.command, now).map(drop);
+            write.result.send(result).ok();
+        }
+
+        // the uptime changes all the time and is only updated with the rest
+        let status = shell.status(now);
+        let report = StatusReport {
+            uptime_ms: 0,
+            ..StatusReport::from(&status)
+        };
+        if self.published.as_ref() == Some(&report) {
+            return;
+        }
+        self.published = Some(report);
+        for (characteristic, handle) in &self.characteristics {
+            if !characteristic.is_readable() {
+                continue;
+            }
+            let mut handle = handle.lock();
+            handle.set_value(&characteristic.read(&status));
+            if characteristic.notifies() {
+                handle.notify();
+            }
+        }
+    }
+}
+
+/// Nordic UART Service bridging generic BLE terminal apps to the text shell
+///
+/// Written bytes are assembled into lines like on the serial port, without echo. The replies go
+/// back to the clients as notifications, while asynchronous events stay on the serial port.
+pub struct UartService {
+    /// Characteristic notifying the replies
+    tx: Arc<Mutex<BLECharacteristic>>,
+    /// Bytes written by clients
+    received: Receiver<Vec<u8>>,
+    /// Assembles the received bytes into lines
+    editor: LineEditor,
+    /// Replies that were not notified yet
+    pending: Vec<u8>,
+}
+
+impl UartService {
+    /// Register the service, it is served once [`Advertising`] has a passkey
+    pub fn new() -> Self {
+        let service = BLEDevice::take()
+            .get_server()
+            .create_service(UART_SERVICE_UUID);
+        let (sender, received) = channel();
+        service
+            .lock()
+            // the whole shell including unlock is behind it, so only paired clients may write
+            .create_characteristic(
+                UART_RX_UUID,
+                NimbleProperties::WRITE
+                    | NimbleProperties::WRITE_NO_RSP
+                    | NimbleProperties::WRITE_ENC
+                    | NimbleProperties::WRITE_AUTHEN,
+            )
+            .lock()
+            .on_write(move |args| {
+                sender.send(args.recv_data().to_vec()).ok();
+            });
+        let tx = service
+            .lock()
+            .create_characteristic(UART_TX_UUID, NimbleProperties::NOTIFY);
+        Self {
+            tx,
+            received,
+            editor: LineEditor::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Execute the received lines and notify the replies
+    pub fn poll(&mut self, shell: &mut Firmware, now: Duration) {
+        let mut reply = String::new();
+        for bytes in self.received.try_iter() {
+            for byte in bytes {
+                // terminal apps show what was sent themselves, so the echo is dropped
+                let mut echo = String::new();
+                let line = self
+                    .editor
+                    .push(byte, &mut echo)
+                    .expect("writing to a string cannot fail");
+                if let Some(line) = line {
+                    shell
+                        .process_command(&line, now, &mut reply)
+                        .expect("writing to a string cannot fail");
+                }
+            }
+        }
+        self.pending.extend_from_slice(reply.as_bytes());
+
+        let length = self
+            .pending
+            .len()
+            .min(UART_CHUNK_LENGTH * UART_CHUNKS_PER_POLL);
+        for chunk in self
+            .pending
+            .drain(..length)
+            .as_slice()
+            .chunks(UART_CHUNK_LENGTH)
+        {
+            self.tx.lock().set_value(chunk).notify();
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use esp_idf_hal::gpio::{AnyIOPin, Input, PinDriver, Pull};
+use esp_idf_sys::EspError;
+
+/// Emergency stop button connecting a GPIO to ground
+pub struct StopButton {
+    /// Driver for the button input
+    driver: PinDriver<'static, AnyIOPin, Input>,
+    /// GPIO the button is connected to
+    pin: u8,
+    /// Whether the button was pressed during the last poll
+    pressed: bool,
+}
+
+impl StopButton {
+    /// Create a new button on the given GPIO.
+    ///
+    /// # Safety
+    ///
+    /// The GPIO must not be used by anything else.
+    pub unsafe fn new(pin: u8) -> Result<Self, EspError> {
+        let mut driver = PinDriver::input(unsafe { AnyIOPin::new(pin as i32) })?;
+        driver.set_pull(Pull::Up)?;
+        Ok(Self {
+            driver,
+            pin,
+            pressed: false,
+        })
+    }
+
+    /// GPIO the button is connected to
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    /// Check whether the button was pressed since the last poll
+    pub fn poll(&mut self) -> bool {
+        let pressed = self.driver.is_low();
+        let was_pressed = self.pressed;
+        self.pressed = pressed;
+        pressed && !was_pressed
+    }
+}
@@ -1,11 +1,33 @@
-use std::ptr::null_mut;
+use std::{
+    io::{stdout, Write},
+    ptr::null_mut,
+    time::Instant,
+};
 
+use caixianlin_core::{
+    binary::{Incoming, Link, DELIMITER},
+    command::Mode,
+    line::LineEditor,
+    shell::Shell,
+};
 use esp_idf_hal::delay::FreeRtos;
-use esp_idf_sys::{esp, esp_vfs_dev_uart_use_driver, uart_driver_install};
+use esp_idf_sys::{
+    esp, esp_line_endings_t_ESP_LINE_ENDINGS_LF, esp_vfs_dev_uart_port_set_rx_line_endings,
+    esp_vfs_dev_uart_use_driver, uart_driver_install,
+};
 
-mod cli;
-mod packet;
-mod queue;
+mod binary;
+mod ble;
+mod button;
+mod receiver;
+mod storage;
+mod transmitter;
+
+use storage::NvsStorage;
+use transmitter::RmtTransmitter;
+
+/// Shell running on the hardware
+type Firmware = Shell<RmtTransmitter, NvsStorage>;
 
 fn main() {
     // setup the peripherals
@@ -14,21 +36,53 @@ fn main() {
     unsafe {
         esp!(uart_driver_install(0, 512, 512, 10, null_mut(), 0)).unwrap();
         esp_vfs_dev_uart_use_driver(0);
+        // pass carriage returns through, binary frames may contain them
+        esp_vfs_dev_uart_port_set_rx_line_endings(0, esp_line_endings_t_ESP_LINE_ENDINGS_LF);
     }
 
     // setup the logger
     esp_idf_svc::log::EspLogger::initialize_default();
     println!("meow :3 arf~");
 
-    // create the transmitter queue and cli state
-    let mut queue = unsafe { queue::Queue::new() };
-    let mut state = cli::State::new();
+    // create the shell with its transmitter queue
+    let boot = Instant::now();
+    let mut shell = Shell::new(unsafe { RmtTransmitter::new() }, NvsStorage::new());
+    shell.version = env!("CARGO_PKG_VERSION");
+    let mut receiver: Option<receiver::Receiver> = None;
+    let mut stop_button: Option<button::StopButton> = None;
+
+    // serve the same shell over BLE, the serial port keeps working without it
+    let mut control = ble::ControlService::new();
+    let mut uart = ble::UartService::new();
+    let mut advertising = ble::Advertising::default();
 
     // main loop
-    let mut buffer = String::new();
+    let mut editor = LineEditor::new();
+    let mut output = String::new();
+    let mut link = Link::new();
+    if shell.mode == Mode::Text {
+        print_prompt(&editor);
+    }
     loop {
-        // tick queue
-        queue.tick();
+        // check the emergency stop before anything else gets transmitted
+        poll_stop_button(&mut stop_button, &mut shell, &mut output);
+
+        // tick queue and learn deadline
+        shell
+            .tick(boot.elapsed(), &mut output)
+            .expect("writing to a string cannot fail");
+
+        // advertise only while BLE has a passkey
+        advertising.poll(&shell);
+
+        // execute what BLE clients wrote, answer them, publish the status and notify replies
+        control.poll(&mut shell, boot.elapsed());
+        uart.poll(&mut shell, boot.elapsed());
+
+        // sniff frames of other remotes
+        poll_receiver(&mut receiver, &mut shell, &mut output);
+        print!("{}", output);
+        output.clear();
 
         // check for input
         // (note: seems to be the only reliable way to read a single character from stdin afaik)
@@ -38,65 +92,136 @@ fn main() {
             continue;
         }
 
-        // process input
-        let char = char::from(char as u8);
-        if char == '\n' {
-            cli::process_command(&buffer, &mut state, &queue);
-            buffer.clear();
-        } else {
-            buffer.push(char);
+        // bytes of binary frames are handled right away to keep the latency low
+        let byte = char as u8;
+        let now = boot.elapsed();
+        if link.is_receiving(now) || byte == DELIMITER {
+            match link.push(byte, now) {
+                Some(Incoming::Request { sequence, request }) => {
+                    let response = shell.process_request(request, now);
+                    binary::write_frame(&link.reply(sequence, &response));
+                }
+                Some(Incoming::Reply(frame)) => binary::write_frame(&frame),
+                None => {}
+            }
+            continue;
         }
 
-        // check too long command
-        if buffer.len() > 100 {
-            longcat();
-            buffer.clear();
+        // process input
+        let line = editor
+            .push(byte, &mut output)
+            .expect("writing to a string cannot fail");
+        // machine mode stays silent apart from replies
+        if shell.mode == Mode::Text {
+            print!("{}", output);
+        }
+        output.clear();
+        if let Some(line) = line {
+            shell
+                .process_command(&line, boot.elapsed(), &mut output)
+                .expect("writing to a string cannot fail");
+            print!("{}", output);
+            output.clear();
+            if shell.mode == Mode::Text {
+                print_prompt(&editor);
+            }
         }
+        stdout().flush().ok();
 
         FreeRtos::delay_ms(1);
     }
 }
 
-/// https://mozz.us/ascii-art/2023-05-01/longcat.html
-fn longcat() {
-    println!(
-        r#"Your command is too looooooooooong
-                           _
-                 __       / |
-                 \ "-..--'_4|_
-      _._____     \ _  _(C "._'._
-     ((^     '"-._( O_ O "._` '. \
-      `"'--._     \  y_     \   \|
-             '-._  \_ _  __.=-.__,\_
-                 `'-(" ,("___       \,_____
-                     (_,("___     .-./     '
-                     |   C'___    (5)
-                     /    ``  '---'-'._```
-                    |     ```    |`    '"-._
-                    |    ````    \-.`
-                    |    ````    |  "._ ``
-                    /    ````    |     '-.__
-                   |     ```     |
-                   |     ```     |
-                   |     ```     |
-                   |     ```     /
-                   |    ````    |
-                   |    ```     |
-                   |    ```     /
-                   |    ```     |
-                   /    ```     |
-                  |     ```     |
-                  |     ```     !
-                  |     ```    / '-.___
-                  |    ````    !_      ''-
-                  /   `   `    | '--._____)
-                  |     /|     !
-                  !    / |     /
-                  |    | |    /
-                  |    | |   /
-                  |    / |   |
-                  /   /  |   |
-                 /   /   |   |
-                (,,_]    (,_,)    mozz"#
-    );
+/// Set up the emergency stop button to match the shell and stop the queue when it is pressed
+fn poll_stop_button(
+    button: &mut Option<button::StopButton>,
+    shell: &mut Firmware,
+    output: &mut String,
+) {
+    // drop the button if it was disabled or moved
+    if button
+        .as_ref()
+        .is_some_and(|button| Some(button.pin()) != shell.stop_pin)
+    {
+        *button = None;
+    }
+    let Some(pin) = shell.stop_pin else {
+        return;
+    };
+
+    // create the button
+    if button.is_none() {
+        match unsafe { button::StopButton::new(pin) } {
+            Ok(new_button) => *button = Some(new_button),
+            Err(error) => {
+                log::warn!("Failed to set up stop button on GPIO {}: {}", pin, error);
+                return;
+            }
+        }
+    }
+    let Some(button) = button else {
+        return;
+    };
+
+    if button.poll() {
+        shell
+            .stop_button(output)
+            .expect("writing to a string cannot fail");
+    }
+}
+
+/// Start, stop or reconfigure the receiver to match the shell and handle everything it received
+fn poll_receiver(
+    receiver: &mut Option<receiver::Receiver>,
+    shell: &mut Firmware,
+    output: &mut String,
+) {
+    // drop the receiver if it is no longer needed or listens on the wrong pin
+    if receiver
+        .as_ref()
+        .is_some_and(|receiver| !shell.is_listening() || receiver.pin() != shell.rx_pin)
+    {
+        *receiver = None;
+    }
+    if !shell.is_listening() {
+        return;
+    }
+
+    // create the receiver
+    if receiver.is_none() {
+        match unsafe { receiver::Receiver::new(shell.rx_pin) } {
+            Ok(new_receiver) => *receiver = Some(new_receiver),
+            Err(error) => {
+                log::warn!(
+                    "Failed to start receiver on GPIO {}: {}",
+                    shell.rx_pin,
+                    error
+                );
+                return;
+            }
+        }
+    }
+    let Some(receiver) = receiver else {
+        return;
+    };
+
+    // print and learn received packets
+    for result in receiver.poll(shell.protocol.protocol()) {
+        match result {
+            Ok(packet) => shell
+                .receive(&packet, output)
+                .expect("writing to a string cannot fail"),
+            Err(error) => log::debug!("Received invalid frame: {}", error),
+        }
+    }
+}
+
+/// Print the prompt and the line being edited
+fn print_prompt(editor: &LineEditor) {
+    let mut prompt = String::new();
+    editor
+        .redraw(&mut prompt)
+        .expect("writing to a string cannot fail");
+    print!("{}", prompt);
+    stdout().flush().ok();
 }
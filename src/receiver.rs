@@ -0,0 +1,110 @@
+use esp_idf_hal::{
+    gpio::AnyIOPin,
+    rmt::{PinState, Pulse, Receive, RmtReceiveConfig, RxRmtDriver, CHANNEL2},
+};
+use esp_idf_sys::EspError;
+
+use caixianlin_core::{
+    decoder::{Decoder, Event},
+    packet::{DecodeError, Packet},
+    protocol::Protocol,
+};
+
+/// Amount of pulse pairs that can be captured at once
+const BUFFER_SIZE: usize = 256;
+
+/// Receiver for sniffing frames of other remotes
+pub struct Receiver {
+    /// Driver for the receiver
+    driver: RxRmtDriver<'static>,
+    /// GPIO the receiver is connected to
+    pin: u8,
+    /// Buffer for captured pulses
+    buffer: Vec<(Pulse, Pulse)>,
+}
+
+impl Receiver {
+    /// Create a new receiver listening on the given GPIO.
+    ///
+    /// # Safety
+    ///
+    /// Only one receiver may exist at a time and the GPIO must not be used by anything else.
+    pub unsafe fn new(pin: u8) -> Result<Self, EspError> {
+        // one tick per microsecond with the 80 MHz APB clock, a capture ends after 5ms of silence
+        let config = RmtReceiveConfig::new()
+            .clock_divider(80)
+            .idle_threshold(5000)
+            .filter_ticks_thresh(200)
+            .filter_en(true);
+
+        let driver = RxRmtDriver::new(
+            unsafe { CHANNEL2::new() },
+            unsafe { AnyIOPin::new(pin as i32) },
+            &config,
+            BUFFER_SIZE * 4,
+        )?;
+        driver.start()?;
+
+        Ok(Self {
+            driver,
+            pin,
+            buffer: vec![(Pulse::zero(), Pulse::zero()); BUFFER_SIZE],
+        })
+    }
+
+    /// GPIO the receiver is connected to
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    /// Decode all frames that were captured since the last poll
+    pub fn poll(&mut self, protocol: &dyn Protocol) -> Vec<Result<Packet, DecodeError>> {
+        let length = match self.driver.receive(&mut self.buffer, 0) {
+            Ok(Receive::Read(length)) => length,
+            Ok(Receive::Overflow(_)) => {
+                log::warn!("Receive buffer overflowed, dropping capture");
+                return Vec::new();
+            }
+            Ok(Receive::Timeout) => return Vec::new(),
+            Err(error) => {
+                log::warn!("Failed to receive: {}", error);
+                return Vec::new();
+            }
+        };
+
+        // flatten the captured items into high/low durations in microseconds
+        let mut durations = Vec::new();
+        let mut high = None;
+        for pulse in self.buffer[..length]
+            .iter()
+            .flat_map(|(first, second)| [first, second])
+        {
+            let ticks = pulse.ticks.ticks() as u32;
+            match (pulse.pin_state, high) {
+                (PinState::High, _) => high = Some(ticks),
+                (PinState::Low, Some(high_ticks)) => {
+                    durations.push((high_ticks, ticks));
+                    high = None;
+                }
+                (PinState::Low, None) => {}
+            }
+        }
+        if let Some(high_ticks) = high {
+            durations.push((high_ticks, 0));
+        }
+
+        Decoder::new(protocol)
+            .decode(&durations)
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Packet { packet, .. } => Some(Ok(packet)),
+                Event::InvalidFrame { error, .. } => Some(Err(error)),
+                Event::BitError { index, high, low } => {
+                    log::debug!("Unexpected pulse {}us/{}us at {}", high, low, index);
+                    None
+                }
+                Event::Sync { .. } => None,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,75 @@
+use caixianlin_core::storage::Storage;
+use esp_idf_svc::{
+    nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault},
+    sys::EspError,
+};
+
+/// Configuration storage in the `sc_config` namespace of the default NVS partition
+pub struct NvsStorage {
+    /// Storage namespace
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl NvsStorage {
+    /// Open the configuration namespace
+    pub fn new() -> Self {
+        let nvs_default_partition = EspDefaultNvsPartition::take().unwrap();
+        let nvs = EspNvs::new(nvs_default_partition, "sc_config", true).unwrap();
+        Self { nvs }
+    }
+}
+
+impl Storage for NvsStorage {
+    fn get_u8(&self, key: &str) -> Option<u8> {
+        self.nvs.get_u8(key).unwrap_or_else(|error| {
+            log::warn!("Failed to read {} from storage: {}", key, error);
+            None
+        })
+    }
+
+    fn get_u16(&self, key: &str) -> Option<u16> {
+        self.nvs.get_u16(key).unwrap_or_else(|error| {
+            log::warn!("Failed to read {} from storage: {}", key, error);
+            None
+        })
+    }
+
+    fn set_u8(&mut self, key: &str, value: u8) {
+        if let Err(error) = self.nvs.set_u8(key, value) {
+            log::warn!("Failed to store {}: {}", key, error);
+        }
+    }
+
+    fn set_u16(&mut self, key: &str, value: u16) {
+        if let Err(error) = self.nvs.set_u16(key, value) {
+            log::warn!("Failed to store {}: {}", key, error);
+        }
+    }
+
+    fn get_blob(&self, key: &str) -> Option<Vec<u8>> {
+        let read = || {
+            let Some(length) = self.nvs.blob_len(key)? else {
+                return Ok(None);
+            };
+            let mut buffer = vec![0; length];
+            let blob = self.nvs.get_blob(key, &mut buffer)?.map(<[u8]>::to_vec);
+            Ok::<_, EspError>(blob)
+        };
+        read().unwrap_or_else(|error| {
+            log::warn!("Failed to read {} from storage: {}", key, error);
+            None
+        })
+    }
+
+    fn set_blob(&mut self, key: &str, value: &[u8]) {
+        if let Err(error) = self.nvs.set_blob(key, value) {
+            log::warn!("Failed to store {}: {}", key, error);
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Err(error) = self.nvs.remove(key) {
+            log::warn!("Failed to remove {} from storage: {}", key, error);
+        }
+    }
+}
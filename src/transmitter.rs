@@ -0,0 +1,97 @@
+use esp_idf_hal::{
+    peripheral::Peripheral,
+    prelude::Peripherals,
+    rmt::{PinState, Pulse, RmtTransmitConfig, TxRmtDriver, VariableLengthSignal},
+    units::Hertz,
+};
+use esp_idf_sys::rmt_register_tx_end_callback;
+use std::{
+    ptr::null_mut,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use caixianlin_core::{protocol::Timing, transmitter::Transmitter};
+
+/// Atomic boolean tracking whether the transmitter is currently transmitting.
+static TRANSMITTING: AtomicBool = AtomicBool::new(false);
+
+/// Callback for when the transmitter finishes transmitting.
+extern "C" fn transmit_finish(_channel: u32, _arg: *mut std::ffi::c_void) {
+    TRANSMITTING.store(false, Ordering::Relaxed);
+}
+
+/// Transmitter using the RMT peripheral on GPIO 0
+pub struct RmtTransmitter {
+    /// Driver for the transmitter
+    driver: TxRmtDriver<'static>,
+    /// Clock frequency of the transmitter
+    ticks_hz: Hertz,
+}
+
+impl RmtTransmitter {
+    /// Create a new transmitter.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if executed more than once!
+    ///
+    pub unsafe fn new() -> Self {
+        // register the transmit finish callback
+        rmt_register_tx_end_callback(Some(transmit_finish), null_mut());
+
+        // create the transmitter
+        let mut config = RmtTransmitConfig::new();
+        config = config
+            .carrier(None)
+            .clock_divider(10)
+            .idle(Some(PinState::Low));
+
+        let mut peripherals = Peripherals::take().unwrap();
+        let driver = TxRmtDriver::new(
+            unsafe { peripherals.rmt.channel1.clone_unchecked() },
+            unsafe { peripherals.pins.gpio0.clone_unchecked() },
+            &config,
+        )
+        .unwrap();
+
+        let ticks_hz = driver.counter_clock().unwrap();
+
+        Self { driver, ticks_hz }
+    }
+}
+
+impl Transmitter for RmtTransmitter {
+    fn transmit(&mut self, symbols: &[Timing]) {
+        let create_pulse = |state, duration: u16| {
+            Pulse::new_with_duration(
+                self.ticks_hz,
+                state,
+                &Duration::from_micros(duration as u64),
+            )
+            .unwrap()
+        };
+
+        // encode the symbols into a signal
+        let mut signal = VariableLengthSignal::with_capacity(2 * symbols.len());
+        for timing in symbols {
+            let high = create_pulse(PinState::High, timing.high);
+            let low = create_pulse(PinState::Low, timing.low);
+            signal.push([&high, &low]).unwrap();
+        }
+
+        TRANSMITTING.store(true, Ordering::Relaxed);
+        self.driver.start(signal).unwrap();
+    }
+
+    fn is_busy(&self) -> bool {
+        TRANSMITTING.load(Ordering::Relaxed)
+    }
+
+    fn abort(&mut self) {
+        if let Err(error) = self.driver.stop() {
+            log::warn!("Failed to abort transmission: {}", error);
+        }
+        TRANSMITTING.store(false, Ordering::Relaxed);
+    }
+}